@@ -0,0 +1,107 @@
+//! Step timings at growing populations.
+//!
+//! Mean of `cargo bench --bench update_step` on one core, before and after the
+//! struct-of-arrays `Population` (before is d877048, the last tree storing a
+//! `Vec<Slime>`, with the same benchmarks). Numbers vary about 20% between runs;
+//! 100k was not run before because a single breeding or die-off step takes minutes.
+//!
+//! | benchmark   | 1k before | 1k after | 10k before | 10k after | 100k before | 100k after |
+//! |-------------|-----------|----------|------------|-----------|-------------|------------|
+//! | update_step | 1.04 ms   | 0.64 ms  | 20.4 ms    | 6.4 ms    | 212 ms      | 54 ms      |
+//! | breeding    | 16.7 ms   | 2.8 ms   | 1689 ms    | 30.3 ms   |             | 358 ms     |
+//! | die_off     | 3.0 ms    | 0.056 ms | 303 ms     | 0.59 ms   |             | 6.7 ms     |
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use evolution_simulation::{scenario::Scenario, simulation::Simulation};
+use macroquad::prelude::*;
+
+/// World area per slime, so every population has the same density.
+const AREA_PER_SLIME: f32 = 400.0;
+
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+fn scenario(slimes: usize) -> Scenario {
+    let side = (slimes as f32 * AREA_PER_SLIME).sqrt();
+    Scenario {
+        seed: Some(0),
+        world_size: Some(vec2(side, side)),
+        initial_slimes: slimes,
+        initial_food: slimes / 10,
+        ..Scenario::default()
+    }
+}
+
+fn simulation(scenario: Scenario) -> Simulation {
+    let slimes = scenario.initial_slimes;
+    let mut simulation = Simulation::new(scenario, Vec2::ONE).unwrap();
+    simulation.food_controller.limit = slimes as f32;
+    simulation
+}
+
+fn update_step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update_step");
+    group.sample_size(10);
+    for slimes in SIZES {
+        group.throughput(Throughput::Elements(slimes as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(slimes), &slimes, |b, &n| {
+            // Each step runs on a fresh world, so the population doesn't change between
+            // iterations
+            let setup = || simulation(scenario(n));
+            b.iter_batched_ref(setup, Simulation::step, BatchSize::LargeInput);
+        });
+    }
+    group.finish();
+}
+
+/// Every slime is ready to breed and the group behaviors, fleeing and collisions are
+/// enabled, as in a grown world, so every neighbour search runs.
+fn breeding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("breeding");
+    group.sample_size(10);
+    for slimes in SIZES {
+        group.throughput(Throughput::Elements(slimes as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(slimes), &slimes, |b, &n| {
+            let setup = || {
+                let mut scenario = scenario(n);
+                scenario.slimes.collisions = true;
+                scenario.slimes.crowding_cost = 0.1;
+                scenario.slimes.behavior.flee_size_ratio = 1.5;
+                scenario.slimes.group.flocking = true;
+                scenario.slimes.group.kin_sharing = true;
+                // Slimes spawn with 3 times the initial energy, enough to breed
+                let initial_energy = scenario.slime.initial_energy;
+                scenario.slime.initial_energy *= 3.0;
+                let mut simulation = simulation(scenario);
+                let controller = &mut simulation.slime_controller;
+                controller.config.initial_energy = initial_energy;
+                simulation.time = controller.breeding_cooldown;
+                simulation
+            };
+            b.iter_batched_ref(setup, Simulation::step, BatchSize::LargeInput);
+        });
+    }
+    group.finish();
+}
+
+/// Every slime dies paying the time cost, as after a long famine.
+fn die_off(c: &mut Criterion) {
+    let mut group = c.benchmark_group("die_off");
+    group.sample_size(10);
+    for slimes in SIZES {
+        group.throughput(Throughput::Elements(slimes as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(slimes), &slimes, |b, &n| {
+            let setup = || {
+                let mut scenario = scenario(n);
+                scenario.slime.initial_energy = 0.5;
+                let mut simulation = simulation(scenario);
+                simulation.time = simulation.slime_controller.time_cost_freq;
+                simulation
+            };
+            b.iter_batched_ref(setup, Simulation::step, BatchSize::LargeInput);
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, update_step, breeding, die_off);
+criterion_main!(benches);
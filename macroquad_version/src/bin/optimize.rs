@@ -0,0 +1,48 @@
+//! Searches the scenario parameters that best meet an objective and writes the best
+//! configs found as scenario files.
+use evolution_simulation::optimizer::Optimizer;
+use std::{fs, path::Path};
+
+fn main() {
+    // Usage: optimize <optimizer.toml> [--output <dir>] [--best <n>]
+    let args = std::env::args().collect::<Vec<_>>();
+    let arg_value = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+    };
+    let path = match args.get(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: optimize <optimizer.toml> [--output <dir>] [--best <n>]");
+            std::process::exit(1);
+        }
+    };
+    let output = Path::new(arg_value("--output").map_or("optimized", |s| s.as_str()));
+    let best = arg_value("--best")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(3);
+    let result = Optimizer::load(path).and_then(|optimizer| {
+        let candidates = optimizer.run(|generation, candidates| {
+            let mean = candidates.iter().map(|c| c.fitness).sum::<f64>() / candidates.len() as f64;
+            println!(
+                "Generation {}: best fitness {:.3}, mean fitness {:.3}",
+                generation, candidates[0].fitness, mean
+            );
+        })?;
+        fs::create_dir_all(output).map_err(|e| e.to_string())?;
+        let base = optimizer.base_scenario()?;
+        for (i, candidate) in candidates.iter().take(best).enumerate() {
+            let path = output.join(format!("best_{}.toml", i + 1));
+            optimizer
+                .candidate_scenario(&base, &candidate.values)?
+                .save(&path)?;
+            println!("{}: fitness {:.3}", path.display(), candidate.fitness);
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        eprintln!("Optimization failed: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,46 @@
+//! Runs a parameter sweep headless and writes the results as CSV.
+use evolution_simulation::sweep::Sweep;
+use std::fs;
+
+fn main() {
+    // Usage: sweep <sweep.toml> [--output <results.csv>]
+    let args = std::env::args().collect::<Vec<_>>();
+    let arg_value = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+    };
+    let path = match args.get(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: sweep <sweep.toml> [--output <results.csv>]");
+            std::process::exit(1);
+        }
+    };
+    let output = arg_value("--output").map_or("sweep.csv", |s| s.as_str());
+    let result = Sweep::load(path).and_then(|sweep| {
+        let runs = sweep.combinations().len() * sweep.seeds.len();
+        println!(
+            "Running {} simulations on {} threads",
+            runs,
+            rayon::current_num_threads()
+        );
+        let results = sweep.run()?;
+        for result in results.iter() {
+            if let Some(e) = &result.frames_error {
+                eprintln!(
+                    "Failed to save frames of run with seed {}: {}",
+                    result.seed, e
+                );
+            }
+        }
+        fs::write(output, sweep.to_csv(&results)).map_err(|e| e.to_string())
+    });
+    match result {
+        Ok(_) => println!("Results written to {}", output),
+        Err(e) => {
+            eprintln!("Sweep failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! # Biome map.
+//!
+//! Grid of terrain cells that modify food spawning and slimes movement and metabolism.
+use crate::rng::gen_range;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Side of the cells when generating a procedural map.
+pub const BIOME_CELL_SIZE: f32 = 20.0;
+/// Distance (in cells) between the random values interpolated by the procedural map.
+const NOISE_SCALE: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Biome {
+    Swamp,
+    Plain,
+    Desert,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 3] = [Biome::Swamp, Biome::Plain, Biome::Desert];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Swamp => "Swamp",
+            Self::Plain => "Plain",
+            Self::Desert => "Desert",
+        }
+    }
+
+    /// Color used to draw the biome and to read it from images.
+    pub fn color(&self) -> Color {
+        match self {
+            Self::Swamp => Color::from_rgba(40, 70, 90, 255),
+            Self::Plain => Color::from_rgba(45, 90, 40, 255),
+            Self::Desert => Color::from_rgba(150, 130, 70, 255),
+        }
+    }
+
+    /// Returns the biome with the closest color.
+    fn from_color(color: Color) -> Self {
+        let distance =
+            |c: Color| (c.r - color.r).powi(2) + (c.g - color.g).powi(2) + (c.b - color.b).powi(2);
+        Self::ALL
+            .into_iter()
+            .min_by(|a, b| {
+                distance(a.color())
+                    .partial_cmp(&distance(b.color()))
+                    .unwrap()
+            })
+            .unwrap()
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// Multipliers applied to the entities inside a biome.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BiomeModifiers {
+    /// Relative chance of food spawning.
+    pub food_spawn: f32,
+    pub step_cost: f32,
+    pub speed: f32,
+    pub vision: f32,
+}
+
+impl Default for BiomeModifiers {
+    fn default() -> Self {
+        Self {
+            food_spawn: 1.0,
+            step_cost: 1.0,
+            speed: 1.0,
+            vision: 1.0,
+        }
+    }
+}
+
+impl BiomeModifiers {
+    pub fn for_biome(biome: Biome) -> Self {
+        match biome {
+            // Plenty of food but hard to move and see
+            Biome::Swamp => Self {
+                food_spawn: 1.5,
+                step_cost: 1.3,
+                speed: 0.6,
+                vision: 0.6,
+            },
+            // Open field, easy to spot food
+            Biome::Plain => Self {
+                vision: 1.3,
+                ..Self::default()
+            },
+            // Scarce food and exhausting to move
+            Biome::Desert => Self {
+                food_spawn: 0.3,
+                step_cost: 1.8,
+                ..Self::default()
+            },
+        }
+    }
+}
+
+pub struct BiomeMap {
+    /// When disabled every position behaves as neutral terrain.
+    pub enabled: bool,
+    /// Modifiers for each biome, indexed as `Biome::ALL`.
+    pub modifiers: [BiomeModifiers; 3],
+    cells: Vec<Biome>,
+    cols: usize,
+    rows: usize,
+    cell_size: Vec2,
+}
+
+impl BiomeMap {
+    /// Creates a map where all cells are `biome`.
+    pub fn uniform(world_size: Vec2, biome: Biome) -> Self {
+        let cols = (world_size.x / BIOME_CELL_SIZE).ceil().max(1.0) as usize;
+        let rows = (world_size.y / BIOME_CELL_SIZE).ceil().max(1.0) as usize;
+        Self {
+            enabled: false,
+            modifiers: Biome::ALL.map(BiomeModifiers::for_biome),
+            cells: vec![biome; cols * rows],
+            cols,
+            rows,
+            cell_size: vec2(BIOME_CELL_SIZE, BIOME_CELL_SIZE),
+        }
+    }
+
+    /// Generates a map from smoothed random noise: low areas become swamps and high
+    /// areas become deserts.
+    pub fn generate(world_size: Vec2) -> Self {
+        let mut map = Self::uniform(world_size, Biome::Plain);
+        let noise_cols = map.cols / NOISE_SCALE + 2;
+        let noise_rows = map.rows / NOISE_SCALE + 2;
+        let noise = (0..noise_cols * noise_rows)
+            .map(|_| gen_range(0.0, 1.0))
+            .collect::<Vec<f32>>();
+        for row in 0..map.rows {
+            for col in 0..map.cols {
+                let (x, y) = (
+                    col as f32 / NOISE_SCALE as f32,
+                    row as f32 / NOISE_SCALE as f32,
+                );
+                let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+                let (tx, ty) = (smoothstep(x.fract()), smoothstep(y.fract()));
+                let at = |c: usize, r: usize| noise[r * noise_cols + c];
+                let top = at(x0, y0) * (1.0 - tx) + at(x0 + 1, y0) * tx;
+                let bottom = at(x0, y0 + 1) * (1.0 - tx) + at(x0 + 1, y0 + 1) * tx;
+                let value = top * (1.0 - ty) + bottom * ty;
+                map.cells[row * map.cols + col] = if value < 0.35 {
+                    Biome::Swamp
+                } else if value < 0.65 {
+                    Biome::Plain
+                } else {
+                    Biome::Desert
+                };
+            }
+        }
+        map.enabled = true;
+        map
+    }
+
+    /// Builds a map where each pixel is one cell stretched to cover the world, the
+    /// biome of each pixel is the one with the closest color (see `Biome::color`).
+    pub fn from_image(image: &image::RgbaImage, world_size: Vec2) -> Result<Self, String> {
+        let (cols, rows) = (image.width() as usize, image.height() as usize);
+        if cols == 0 || rows == 0 {
+            return Err("The biome map image is empty".to_string());
+        }
+        let cells = image
+            .pixels()
+            .map(|&image::Rgba([r, g, b, a])| Biome::from_color(Color::from_rgba(r, g, b, a)))
+            .collect();
+        Ok(Self {
+            enabled: true,
+            modifiers: Biome::ALL.map(BiomeModifiers::for_biome),
+            cells,
+            cols,
+            rows,
+            cell_size: vec2(world_size.x / cols as f32, world_size.y / rows as f32),
+        })
+    }
+
+    /// Loads a map from a PNG image (see `from_image`).
+    pub fn load(path: impl AsRef<Path>, world_size: Vec2) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        let image = image::load_from_memory(&bytes)
+            .map_err(|e| e.to_string())?
+            .to_rgba8();
+        Self::from_image(&image, world_size)
+    }
+
+    /// Returns the biome at `position`, positions outside the map use the closest cell.
+    pub fn biome(&self, position: Vec2) -> Biome {
+        let col = ((position.x / self.cell_size.x).max(0.0) as usize).min(self.cols - 1);
+        let row = ((position.y / self.cell_size.y).max(0.0) as usize).min(self.rows - 1);
+        self.cells[row * self.cols + col]
+    }
+
+    /// Returns the modifiers to apply at `position`.
+    pub fn modifiers(&self, position: Vec2) -> BiomeModifiers {
+        if self.enabled {
+            self.modifiers[self.biome(position).index()]
+        } else {
+            BiomeModifiers::default()
+        }
+    }
+
+    /// Randomly accepts food spawning at `position` proportionally to its biome
+    /// `food_spawn` modifier.
+    pub fn accept_food_spawn(&self, position: Vec2) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        let max = self
+            .modifiers
+            .iter()
+            .map(|m| m.food_spawn)
+            .fold(0.0, f32::max);
+        max > 0.0 && gen_range(0.0, max) < self.modifiers(position).food_spawn
+    }
+
+    pub fn draw(&self) {
+        if !self.enabled {
+            return;
+        }
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                let mut color = self.cells[row * self.cols + col].color();
+                color.a = 0.35;
+                draw_rectangle(
+                    col as f32 * self.cell_size.x,
+                    row as f32 * self.cell_size.y,
+                    self.cell_size.x,
+                    self.cell_size.y,
+                    color,
+                );
+            }
+        }
+    }
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_image_works() {
+        let mut image = image::RgbaImage::from_pixel(2, 1, image::Rgba([150, 130, 70, 255]));
+        image.put_pixel(0, 0, image::Rgba([40, 60, 100, 255]));
+        let map = BiomeMap::from_image(&image, vec2(100.0, 50.0)).unwrap();
+        assert_eq!(map.biome(vec2(10.0, 10.0)), Biome::Swamp);
+        assert_eq!(map.biome(vec2(60.0, 10.0)), Biome::Desert);
+        // Outside the map uses the closest cell
+        assert_eq!(map.biome(vec2(500.0, -10.0)), Biome::Desert);
+        assert_eq!(
+            map.modifiers(vec2(60.0, 10.0)),
+            BiomeModifiers::for_biome(Biome::Desert)
+        );
+        let empty = image::RgbaImage::new(0, 0);
+        assert!(BiomeMap::from_image(&empty, vec2(100.0, 50.0)).is_err());
+    }
+
+    #[test]
+    fn disabled_map_is_neutral() {
+        let mut map = BiomeMap::generate(vec2(200.0, 200.0));
+        map.enabled = false;
+        assert_eq!(map.modifiers(vec2(10.0, 10.0)), BiomeModifiers::default());
+        assert!(map.accept_food_spawn(vec2(10.0, 10.0)));
+    }
+}
@@ -0,0 +1,165 @@
+//! # World camera.
+//!
+//! Maps world positions to the screen, so the world can be zoomed and panned
+//! independently of the window size.
+use macroquad::prelude::*;
+
+const MIN_ZOOM: f32 = 0.05;
+const MAX_ZOOM: f32 = 50.0;
+
+pub struct WorldCamera {
+    /// World position shown at the center of the screen.
+    pub target: Vec2,
+    /// Screen pixels per world unit.
+    pub zoom: f32,
+    /// Screen position where the current drag started, and the target at that time.
+    drag: Option<(Vec2, Vec2)>,
+}
+
+impl Default for WorldCamera {
+    fn default() -> Self {
+        Self {
+            target: Vec2::ZERO,
+            zoom: 1.0,
+            drag: None,
+        }
+    }
+}
+
+impl WorldCamera {
+    /// Shows the whole world centered on the screen.
+    pub fn fit(&mut self, world_size: Vec2, screen_size: Vec2) {
+        self.target = world_size / 2.0;
+        self.zoom = (screen_size / world_size)
+            .min_element()
+            .clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    pub fn world_to_screen(&self, point: Vec2, screen_size: Vec2) -> Vec2 {
+        (point - self.target) * self.zoom + screen_size / 2.0
+    }
+
+    pub fn screen_to_world(&self, point: Vec2, screen_size: Vec2) -> Vec2 {
+        (point - screen_size / 2.0) / self.zoom + self.target
+    }
+
+    /// Multiplies the zoom by `factor` keeping the world position under `point` (in
+    /// screen coordinates) in place.
+    pub fn zoom_at(&mut self, point: Vec2, factor: f32, screen_size: Vec2) {
+        let anchor = self.screen_to_world(point, screen_size);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.target = anchor - (point - screen_size / 2.0) / self.zoom;
+    }
+
+    pub fn start_drag(&mut self, mouse: Vec2) {
+        self.drag = Some((mouse, self.target));
+    }
+
+    /// Pans following the mouse from the position it was when the drag started.
+    pub fn drag(&mut self, mouse: Vec2) {
+        if let Some((start, target)) = self.drag {
+            self.target = target - (mouse - start) / self.zoom;
+        }
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag = None;
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// Screen position where the current drag started.
+    pub fn drag_start(&self) -> Option<Vec2> {
+        self.drag.map(|(start, _)| start)
+    }
+
+    /// Offsets of the copies of a wrapping world that are visible, drawing the world at
+    /// each of them shows what lies across the edges.
+    pub fn tile_offsets(&self, world_size: Vec2, screen_size: Vec2) -> Vec<Vec2> {
+        let min = (self.screen_to_world(Vec2::ZERO, screen_size) / world_size).floor();
+        let max = (self.screen_to_world(screen_size, screen_size) / world_size).floor();
+        let mut offsets = Vec::new();
+        for y in min.y as i32..=max.y as i32 {
+            for x in min.x as i32..=max.x as i32 {
+                offsets.push(vec2(x as f32, y as f32) * world_size);
+            }
+        }
+        offsets
+    }
+
+    /// Copy of `point` in a wrapping world closest to the center of the screen.
+    pub fn nearest_copy(&self, point: Vec2, world_size: Vec2) -> Vec2 {
+        let diff = point - self.target;
+        self.target + diff - (diff / world_size).round() * world_size
+    }
+
+    /// Camera to draw the world with macroquad.
+    pub fn camera_2d(&self, screen_size: Vec2) -> Camera2D {
+        Camera2D {
+            target: self.target,
+            zoom: vec2(2.0, -2.0) * self.zoom / screen_size,
+            ..Camera2D::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_works() {
+        let screen = vec2(800.0, 600.0);
+        let mut camera = WorldCamera::default();
+        camera.fit(vec2(1600.0, 600.0), screen);
+        assert_eq!(camera.zoom, 0.5);
+        assert_eq!(
+            camera.world_to_screen(vec2(0.0, 300.0), screen),
+            vec2(0.0, 300.0)
+        );
+        let point = vec2(100.0, 200.0);
+        assert_eq!(
+            camera.screen_to_world(camera.world_to_screen(point, screen), screen),
+            point
+        );
+        // Zooming keeps the point under the mouse
+        let mouse = vec2(200.0, 100.0);
+        let before = camera.screen_to_world(mouse, screen);
+        camera.zoom_at(mouse, 4.0, screen);
+        assert_eq!(camera.zoom, 2.0);
+        assert_eq!(camera.screen_to_world(mouse, screen), before);
+        // Dragging moves the world with the mouse
+        let grabbed = camera.screen_to_world(vec2(10.0, 10.0), screen);
+        camera.start_drag(vec2(10.0, 10.0));
+        assert_eq!(camera.drag_start(), Some(vec2(10.0, 10.0)));
+        camera.drag(vec2(20.0, 10.0));
+        camera.drag(vec2(30.0, 10.0));
+        camera.end_drag();
+        assert_eq!(camera.screen_to_world(vec2(30.0, 10.0), screen), grabbed);
+        assert!(!camera.is_dragging());
+    }
+
+    #[test]
+    fn wrapping_works() {
+        let screen = vec2(100.0, 100.0);
+        let world = vec2(1000.0, 1000.0);
+        let camera = WorldCamera {
+            target: vec2(990.0, 500.0),
+            ..WorldCamera::default()
+        };
+        assert_eq!(
+            camera.tile_offsets(world, screen),
+            vec![Vec2::ZERO, vec2(1000.0, 0.0)]
+        );
+        assert_eq!(
+            camera.nearest_copy(vec2(5.0, 500.0), world),
+            vec2(1005.0, 500.0)
+        );
+        assert_eq!(
+            camera.nearest_copy(vec2(900.0, 450.0), world),
+            vec2(900.0, 450.0)
+        );
+    }
+}
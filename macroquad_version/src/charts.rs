@@ -0,0 +1,152 @@
+//! # Charts.
+//!
+//! Line, stacked area, histogram and scatter charts drawn inside UI windows with the
+//! canvas primitives.
+use macroquad::{prelude::*, ui::canvas::DrawCanvas};
+
+const BACKGROUND: Color = Color::new(0.0, 0.0, 0.0, 0.6);
+const BORDER: Color = GRAY;
+
+/// Points of a chart of `values` inside `rect`, scaled so `max` is at the top.
+fn chart_points(values: &[f32], max: f32, rect: Rect) -> Vec<Vec2> {
+    let step = rect.w / (values.len().max(2) - 1) as f32;
+    let max = if max > 0.0 { max } else { 1.0 };
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, &value)| {
+            vec2(
+                rect.x + i as f32 * step,
+                rect.y + rect.h * (1.0 - value / max),
+            )
+        })
+        .collect()
+}
+
+/// Cumulative sums of the series, so each one can be drawn over the previous ones.
+fn stack(series: &[&[f32]]) -> Vec<Vec<f32>> {
+    let mut totals = vec![0.0; series.iter().map(|s| s.len()).max().unwrap_or(0)];
+    series
+        .iter()
+        .map(|values| {
+            totals
+                .iter_mut()
+                .zip(values.iter())
+                .for_each(|(total, value)| *total += value);
+            totals.clone()
+        })
+        .collect()
+}
+
+/// Requests space for a chart in the window and draws its background.
+fn chart_rect(canvas: &mut DrawCanvas, size: Vec2) -> Rect {
+    let position = canvas.request_space(size);
+    let rect = Rect::new(position.x, position.y, size.x, size.y);
+    canvas.rect(rect, BORDER, BACKGROUND);
+    rect
+}
+
+/// Draws `values` as a line scaled from 0 to its maximum.
+pub fn line_chart(canvas: &mut DrawCanvas, size: Vec2, values: &[f32], color: Color) {
+    let rect = chart_rect(canvas, size);
+    let max = values.iter().cloned().fold(0.0, f32::max);
+    let points = chart_points(values, max, rect);
+    for pair in points.windows(2) {
+        canvas.line(pair[0], pair[1], color);
+    }
+}
+
+/// Draws the series stacked on top of each other, filling the area under each one with
+/// a column for every value.
+pub fn stacked_area_chart(canvas: &mut DrawCanvas, size: Vec2, series: &[(&[f32], Color)]) {
+    let rect = chart_rect(canvas, size);
+    let values = series.iter().map(|(values, _)| *values).collect::<Vec<_>>();
+    let stacked = stack(&values);
+    let max = stacked
+        .last()
+        .map_or(0.0, |totals| totals.iter().cloned().fold(0.0, f32::max));
+    let width = rect.w / stacked.first().map_or(1, |s| s.len().max(1)) as f32;
+    // The top series first, the ones below are drawn over it
+    for (totals, (_, color)) in stacked.iter().zip(series.iter()).rev() {
+        for (point, &total) in chart_points(totals, max, rect).iter().zip(totals) {
+            if total > 0.0 {
+                let x = (point.x - width / 2.0).clamp(rect.x, rect.x + rect.w - width);
+                canvas.rect(
+                    Rect::new(x, point.y, width, rect.y + rect.h - point.y),
+                    None,
+                    *color,
+                );
+            }
+        }
+    }
+}
+
+/// Draws a bar for each count, scaled to the highest one.
+pub fn histogram_chart(canvas: &mut DrawCanvas, size: Vec2, counts: &[usize], color: Color) {
+    let rect = chart_rect(canvas, size);
+    let max = counts.iter().cloned().max().unwrap_or(0).max(1) as f32;
+    let width = rect.w / counts.len().max(1) as f32;
+    for (i, &count) in counts.iter().enumerate() {
+        if count > 0 {
+            let height = rect.h * count as f32 / max;
+            canvas.rect(
+                Rect::new(
+                    rect.x + i as f32 * width,
+                    rect.y + rect.h - height,
+                    (width - 1.0).max(1.0),
+                    height,
+                ),
+                None,
+                color,
+            );
+        }
+    }
+}
+
+/// Draws a square at each point (x, y from 0 to `max`) with an area proportional to
+/// its count, for integer values where many points overlap.
+pub fn count_scatter_chart(
+    canvas: &mut DrawCanvas,
+    size: Vec2,
+    points: impl Iterator<Item = ((usize, usize), usize)> + Clone,
+    max: (usize, usize),
+    color: Color,
+) {
+    let rect = chart_rect(canvas, size);
+    let max_count = points
+        .clone()
+        .map(|(_, count)| count)
+        .max()
+        .unwrap_or(0)
+        .max(1) as f32;
+    let cell = vec2(rect.w / (max.0 + 1) as f32, rect.h / (max.1 + 1) as f32);
+    for ((x, y), count) in points {
+        let side = cell.min_element() * (count as f32 / max_count).sqrt();
+        let center = vec2(
+            rect.x + (x as f32 + 0.5) * cell.x,
+            rect.y + rect.h - (y as f32 + 0.5) * cell.y,
+        );
+        canvas.rect(
+            Rect::new(center.x - side / 2.0, center.y - side / 2.0, side, side),
+            None,
+            color,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chart_works() {
+        let rect = Rect::new(0.0, 0.0, 100.0, 10.0);
+        let points = chart_points(&[0.0, 5.0, 10.0], 10.0, rect);
+        assert_eq!(
+            points,
+            vec![vec2(0.0, 10.0), vec2(50.0, 5.0), vec2(100.0, 0.0)]
+        );
+        let stacked = stack(&[&[1.0, 2.0], &[3.0, 4.0]]);
+        assert_eq!(stacked, vec![vec![1.0, 2.0], vec![4.0, 6.0]]);
+    }
+}
@@ -0,0 +1,144 @@
+//! # Simulation clock.
+//!
+//! Decouples the simulation speed from the frame rate: real time is accumulated and
+//! converted into fixed steps, without spending more than a time budget per frame so
+//! the window stays responsive at large populations.
+
+/// Frame times are clamped to this (seconds), so a long pause (e.g. dragging the
+/// window) doesn't queue a burst of steps.
+const MAX_FRAME_TIME: f64 = 0.25;
+/// How often (seconds) the achieved steps per second are measured.
+const RATE_WINDOW: f64 = 0.5;
+
+pub struct Clock {
+    /// Simulation steps per second of real time.
+    pub steps_per_second: f32,
+    /// Maximum time (milliseconds) spent simulating each frame, steps that don't fit
+    /// are dropped.
+    pub budget: f32,
+    /// Simulate as fast as possible, redrawing only `max_speed_fps` times per second.
+    pub max_speed: bool,
+    pub max_speed_fps: f32,
+    /// No steps run while paused.
+    pub paused: bool,
+    /// Steps waiting to run, the fraction carries over to the next frame.
+    accumulator: f64,
+    last_tick: Option<f64>,
+    /// Steps per second measured over the last `RATE_WINDOW`.
+    achieved: f32,
+    window_start: f64,
+    window_steps: usize,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self {
+            steps_per_second: 60.0,
+            budget: 12.0,
+            max_speed: false,
+            max_speed_fps: 5.0,
+            paused: false,
+            accumulator: 0.0,
+            last_tick: None,
+            achieved: 0.0,
+            window_start: 0.0,
+            window_steps: 0,
+        }
+    }
+}
+
+impl Clock {
+    /// Runs the steps due since the last tick calling `step`, which returns false when
+    /// the simulation can't advance. `now` gives the current time in seconds.
+    ///
+    /// In max speed mode it keeps stepping for a whole frame of `max_speed_fps`.
+    /// Returns the number of steps run.
+    pub fn tick(&mut self, now: impl Fn() -> f64, mut step: impl FnMut() -> bool) -> usize {
+        let start = now();
+        let frame_time = self
+            .last_tick
+            .map_or(0.0, |last| (start - last).min(MAX_FRAME_TIME));
+        self.last_tick = Some(start);
+        let (due, budget) = if self.paused {
+            self.accumulator = 0.0;
+            (0.0, 0.0)
+        } else if self.max_speed {
+            self.accumulator = 0.0;
+            (f64::INFINITY, 1.0 / self.max_speed_fps.max(1.0) as f64)
+        } else {
+            self.accumulator += frame_time * self.steps_per_second as f64;
+            (self.accumulator.floor(), self.budget as f64 / 1000.0)
+        };
+
+        let mut steps = 0;
+        while ((steps as f64) < due) && ((now() - start) < budget) && step() {
+            steps += 1;
+        }
+        if !(self.paused || self.max_speed) {
+            self.accumulator -= steps as f64;
+            if (steps as f64) < due {
+                // Drop the steps that didn't fit
+                self.accumulator = self.accumulator.fract();
+            }
+        }
+        self.measure(now(), steps);
+        steps
+    }
+
+    fn measure(&mut self, now: f64, steps: usize) {
+        self.window_steps += steps;
+        let elapsed = now - self.window_start;
+        if elapsed >= RATE_WINDOW {
+            self.achieved = (self.window_steps as f64 / elapsed) as f32;
+            self.window_start = now;
+            self.window_steps = 0;
+        }
+    }
+
+    /// Steps per second actually simulated.
+    pub fn achieved(&self) -> f32 {
+        self.achieved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn clock_works() {
+        let time = Cell::new(0.0);
+        let now = || time.get();
+        let mut clock = Clock {
+            steps_per_second: 100.0,
+            ..Clock::default()
+        };
+        // Cheap steps follow the requested speed, fractions carry over
+        let mut total = 0;
+        for _ in 0..10 {
+            total += clock.tick(now, || true);
+            time.set(time.get() + 0.015);
+        }
+        assert_eq!(total, 13);
+        // Expensive steps are limited by the budget (12ms)
+        let slow_step = || {
+            time.set(time.get() + 0.005);
+            true
+        };
+        time.set(time.get() + 0.1);
+        assert_eq!(clock.tick(now, slow_step), 3);
+        // Stopped or paused simulations don't advance
+        time.set(time.get() + 0.1);
+        assert_eq!(clock.tick(now, || false), 0);
+        clock.paused = true;
+        time.set(time.get() + 0.1);
+        assert_eq!(clock.tick(now, || true), 0);
+        clock.paused = false;
+        // Max speed runs a whole redraw frame
+        clock.max_speed = true;
+        clock.max_speed_fps = 4.0;
+        assert!((49..=51).contains(&clock.tick(now, slow_step)));
+        assert!(clock.achieved() > 0.0);
+    }
+}
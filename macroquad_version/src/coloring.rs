@@ -0,0 +1,276 @@
+//! # Slime colors.
+//!
+//! Colors the slimes by state, skills, lineage or a gradient of a value, with the legend
+//! explaining them. Shared by the window and the image renderer.
+use crate::slime::{SkillType, SlimeController, SlimeState};
+use macroquad::{color::hsl_to_rgb, prelude::*};
+use serde::Deserialize;
+use std::{cmp::Reverse, collections::BTreeMap};
+
+/// Ends of the gradients, from the lowest value to the highest.
+const GRADIENT: (Color, Color) = (
+    Color::new(0.2, 0.4, 1.0, 1.0),
+    Color::new(1.0, 0.85, 0.1, 1.0),
+);
+const NO_SKILL_COLOR: Color = GRAY;
+/// Outline of infected slimes.
+pub const INFECTED_COLOR: Color = Color::new(0.6, 1.0, 0.0, 1.0);
+/// Largest lineages shown in the legend.
+const LEGEND_FOUNDERS: usize = 5;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorMode {
+    #[default]
+    State,
+    DominantSkill,
+    SkillPath,
+    /// Mix of the skill colors weighted by their levels.
+    SkillMix,
+    Lineage,
+    Energy,
+    Age,
+    Generation,
+}
+
+impl ColorMode {
+    pub const ALL: [ColorMode; 8] = [
+        ColorMode::State,
+        ColorMode::DominantSkill,
+        ColorMode::SkillPath,
+        ColorMode::SkillMix,
+        ColorMode::Lineage,
+        ColorMode::Energy,
+        ColorMode::Age,
+        ColorMode::Generation,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::State => "State",
+            Self::DominantSkill => "Dominant skill",
+            Self::SkillPath => "Skill path",
+            Self::SkillMix => "Skill mix",
+            Self::Lineage => "Lineage",
+            Self::Energy => "Energy",
+            Self::Age => "Age",
+            Self::Generation => "Generation",
+        }
+    }
+}
+
+/// Colors of the slimes of a controller at a time.
+pub struct Palette<'a> {
+    mode: ColorMode,
+    controller: &'a SlimeController,
+    time: f32,
+    /// Lowest and highest values of the gradient modes.
+    range: (f32, f32),
+}
+
+impl<'a> Palette<'a> {
+    pub fn new(mode: ColorMode, controller: &'a SlimeController, time: f32) -> Self {
+        let mut palette = Self {
+            mode,
+            controller,
+            time,
+            range: (0.0, 0.0),
+        };
+        let values = (0..controller.population.len()).map(|i| palette.value(i));
+        palette.range = values.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+        palette
+    }
+
+    /// Value of the slime `i` for the gradient modes.
+    fn value(&self, i: usize) -> f32 {
+        let population = &self.controller.population;
+        match self.mode {
+            ColorMode::Energy => population.energy[i],
+            ColorMode::Age => self.time - population.born[i],
+            ColorMode::Generation => population.generation[i] as f32,
+            _ => 0.0,
+        }
+    }
+
+    pub fn color(&self, i: usize) -> Color {
+        let population = &self.controller.population;
+        let skills = &population.skills[i];
+        let levels = [skills.vision, skills.efficiency, skills.jumper];
+        match self.mode {
+            ColorMode::State => {
+                let controller = self.controller;
+                if population.is_breed_ready(
+                    i,
+                    self.time,
+                    controller.breeding_cooldown,
+                    &controller.config,
+                ) {
+                    PINK
+                } else {
+                    match population.state[i] {
+                        SlimeState::Normal => RED,
+                        SlimeState::Jumping => LIME,
+                        SlimeState::Breeding => VIOLET,
+                    }
+                }
+            }
+            ColorMode::DominantSkill => {
+                dominant_skill(levels).map_or(NO_SKILL_COLOR, |s| s.color())
+            }
+            ColorMode::SkillPath => population.skill_path[i].color(),
+            ColorMode::SkillMix => skill_mix(levels),
+            ColorMode::Lineage => lineage_color(population.founder[i]),
+            ColorMode::Energy | ColorMode::Age | ColorMode::Generation => {
+                let (min, max) = self.range;
+                gradient((self.value(i) - min) / (max - min).max(f32::EPSILON))
+            }
+        }
+    }
+
+    /// Labels and colors explaining the mode.
+    pub fn legend(&self) -> Vec<(String, Color)> {
+        let skills = || {
+            SkillType::ALL
+                .iter()
+                .map(|skill| (format!("{:?}", skill), skill.color()))
+        };
+        match self.mode {
+            ColorMode::State => vec![
+                ("Normal".to_string(), RED),
+                ("Jumping".to_string(), LIME),
+                ("Breeding".to_string(), VIOLET),
+                ("Breed ready".to_string(), PINK),
+            ],
+            ColorMode::DominantSkill => skills()
+                .chain([("No skills".to_string(), NO_SKILL_COLOR)])
+                .collect(),
+            ColorMode::SkillPath => skills()
+                .map(|(name, color)| (format!("{} path", name), color))
+                .collect(),
+            ColorMode::SkillMix => skills()
+                .map(|(name, color)| (format!("{} levels", name), color))
+                .chain([("No skills".to_string(), NO_SKILL_COLOR)])
+                .collect(),
+            ColorMode::Lineage => {
+                largest_lineages(&self.controller.population.founder, LEGEND_FOUNDERS)
+                    .into_iter()
+                    .map(|(founder, count)| {
+                        (
+                            format!("Founder {}: {} slimes", founder, count),
+                            lineage_color(founder),
+                        )
+                    })
+                    .collect()
+            }
+            ColorMode::Energy | ColorMode::Age | ColorMode::Generation => {
+                if self.controller.population.is_empty() {
+                    return Vec::new();
+                }
+                let (min, max) = self.range;
+                vec![
+                    (format!("{} {:.0}", self.mode.name(), min), gradient(0.0)),
+                    (format!("{} {:.0}", self.mode.name(), max), gradient(1.0)),
+                ]
+            }
+        }
+    }
+}
+
+/// Skill with the most levels, the first one on ties and none without levels.
+fn dominant_skill(levels: [usize; 3]) -> Option<SkillType> {
+    let (i, &max) = levels
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|(_, &level)| level)?;
+    (max > 0).then(|| SkillType::ALL[i])
+}
+
+/// Mix of the skill colors weighted by their levels.
+fn skill_mix(levels: [usize; 3]) -> Color {
+    let total = levels.iter().sum::<usize>();
+    if total == 0 {
+        return NO_SKILL_COLOR;
+    }
+    let mut color = Color::new(0.0, 0.0, 0.0, 1.0);
+    for (skill, &level) in SkillType::ALL.iter().zip(levels.iter()) {
+        let weight = level as f32 / total as f32;
+        let skill_color = skill.color();
+        color.r += skill_color.r * weight;
+        color.g += skill_color.g * weight;
+        color.b += skill_color.b * weight;
+    }
+    color
+}
+
+/// A hue for each founder, consecutive ids get distant hues.
+fn lineage_color(founder: usize) -> Color {
+    hsl_to_rgb((founder as f32 * 0.618_034).fract(), 0.8, 0.55)
+}
+
+/// Color at `t` (0 to 1) of the gradient.
+fn gradient(t: f32) -> Color {
+    let (low, high) = GRADIENT;
+    let t = t.clamp(0.0, 1.0);
+    Color::new(
+        low.r + (high.r - low.r) * t,
+        low.g + (high.g - low.g) * t,
+        low.b + (high.b - low.b) * t,
+        1.0,
+    )
+}
+
+/// The `n` founders with the most descendants alive and their counts, largest first.
+fn largest_lineages(founders: &[usize], n: usize) -> Vec<(usize, usize)> {
+    let mut counts = BTreeMap::new();
+    for &founder in founders {
+        *counts.entry(founder).or_insert(0) += 1;
+    }
+    let mut lineages = counts.into_iter().collect::<Vec<_>>();
+    lineages.sort_by_key(|&(_, count)| Reverse(count));
+    lineages.truncate(n);
+    lineages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{obstacle::ObstacleMap, slime::SlimeConfig};
+
+    #[test]
+    fn palette_works() {
+        assert_eq!(dominant_skill([0, 0, 0]), None);
+        assert_eq!(dominant_skill([1, 3, 3]), Some(SkillType::Efficiency));
+        assert_eq!(skill_mix([2, 0, 0]), SkillType::Vision.color());
+        assert_eq!(
+            largest_lineages(&[4, 1, 4, 2, 2, 4], 2),
+            vec![(4, 3), (2, 2)]
+        );
+
+        let mut controller =
+            SlimeController::new(vec2(100.0, 100.0), SlimeConfig::default(), 1.0, 150.0, 0.5);
+        controller.spawn_with_skills(2, (0, 0, 0).into(), &ObstacleMap::default());
+        controller.population.energy[0] = 10.0;
+        controller.population.energy[1] = 30.0;
+        let palette = Palette::new(ColorMode::Energy, &controller, 0.0);
+        assert_eq!(palette.color(0), gradient(0.0));
+        assert_eq!(palette.color(1), gradient(1.0));
+        assert_eq!(palette.legend()[1].0, "Energy 30");
+        controller.population.skill_path[0] = SkillType::Jumper;
+        let palette = Palette::new(ColorMode::SkillPath, &controller, 0.0);
+        assert_eq!(palette.color(0), SkillType::Jumper.color());
+        let legend = palette.legend();
+        assert_eq!(legend.len(), 3);
+        assert_eq!(
+            legend[2],
+            ("Jumper path".to_string(), SkillType::Jumper.color())
+        );
+        let palette = Palette::new(ColorMode::SkillMix, &controller, 0.0);
+        assert_eq!(palette.color(0), NO_SKILL_COLOR);
+        let palette = Palette::new(ColorMode::Lineage, &controller, 0.0);
+        assert_ne!(palette.color(0), palette.color(1));
+        assert_eq!(palette.legend().len(), 2);
+    }
+}
@@ -0,0 +1,95 @@
+//! # Disease.
+//!
+//! Optional pathogen that spreads between slimes following a SIR model: susceptible
+//! slimes get infected by contact or breeding, infected slimes lose extra energy
+//! every step and after a while recover with immunity.
+use crate::rng::gen_range;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Health {
+    Susceptible,
+    /// Infected until the given time.
+    Infected(f32),
+    Recovered,
+}
+
+impl Health {
+    pub fn is_infected(&self) -> bool {
+        matches!(self, Self::Infected(_))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiseaseConfig {
+    pub enabled: bool,
+    /// Extra energy lost every step by infected slimes.
+    pub energy_cost: f32,
+    /// Chance of infection per step when touching an infected slime.
+    pub contact_chance: f32,
+    /// Chance of infection when breeding with an infected slime (also for the child).
+    pub breed_chance: f32,
+    /// Time steps until an infected slime recovers.
+    pub duration: f32,
+}
+
+impl Default for DiseaseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            energy_cost: 0.05,
+            contact_chance: 0.05,
+            breed_chance: 0.5,
+            duration: 2000.0,
+        }
+    }
+}
+
+impl DiseaseConfig {
+    /// Rolls an infection with `chance` reduced by `resistance` (in the range [0, 1]).
+    /// Returns the new health, only susceptible slimes can get infected.
+    pub fn try_infect(&self, health: Health, resistance: f32, chance: f32, time: f32) -> Health {
+        if self.enabled
+            && (health == Health::Susceptible)
+            && (gen_range(0.0, 1.0) < chance * (1.0 - resistance))
+        {
+            Health::Infected(time + self.duration)
+        } else {
+            health
+        }
+    }
+
+    /// Returns the health after one step, infected slimes recover when their time is over.
+    pub fn update_health(&self, health: Health, time: f32) -> Health {
+        match health {
+            Health::Infected(until) if time >= until => Health::Recovered,
+            health => health,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_infect_works() {
+        let mut config = DiseaseConfig::default();
+        let health = config.try_infect(Health::Susceptible, 0.0, 1.0, 0.0);
+        assert_eq!(health, Health::Susceptible, "Disabled disease can't infect");
+        config.enabled = true;
+        let health = config.try_infect(Health::Susceptible, 0.0, 1.0, 0.0);
+        assert_eq!(health, Health::Infected(config.duration));
+        let health = config.try_infect(Health::Susceptible, 1.0, 1.0, 0.0);
+        assert_eq!(
+            health,
+            Health::Susceptible,
+            "Full resistance avoids infection"
+        );
+        let health = config.try_infect(Health::Recovered, 0.0, 1.0, 0.0);
+        assert_eq!(health, Health::Recovered, "Recovered slimes are immune");
+        let health = config.update_health(Health::Infected(10.0), 10.0);
+        assert_eq!(health, Health::Recovered);
+    }
+}
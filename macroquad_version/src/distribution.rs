@@ -0,0 +1,108 @@
+//! # Population distributions.
+//!
+//! Histograms of the slime population, showing how it spreads beyond the averages
+//! of `stats`.
+use crate::population::Population;
+use std::collections::BTreeMap;
+
+/// Number of bins of the continuous histograms.
+const BINS: usize = 20;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Histogram {
+    /// Lower bound of the first bin.
+    pub min: f32,
+    /// Upper bound of the last bin.
+    pub max: f32,
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    /// Splits the range of `values` in `bins` bins of the same width.
+    pub fn new(values: &[f32], bins: usize) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        let min = values.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let width = (max - min).max(f32::EPSILON) / bins as f32;
+        let mut counts = vec![0; bins];
+        for value in values {
+            let bin = ((value - min) / width) as usize;
+            counts[bin.min(bins - 1)] += 1;
+        }
+        Self { min, max, counts }
+    }
+
+    /// One bin for each level from 0 to the highest one.
+    pub fn levels(levels: impl Iterator<Item = usize>) -> Self {
+        let mut counts = Vec::new();
+        for level in levels {
+            if level >= counts.len() {
+                counts.resize(level + 1, 0);
+            }
+            counts[level] += 1;
+        }
+        Self {
+            min: 0.0,
+            max: counts.len() as f32,
+            counts,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Distributions {
+    /// Time when they were collected.
+    pub time: f32,
+    pub energy: Histogram,
+    pub size: Histogram,
+    pub age: Histogram,
+    pub vision: Histogram,
+    pub efficiency: Histogram,
+    pub jumper: Histogram,
+    /// Number of slimes with each (vision, efficiency) levels.
+    pub vision_efficiency: BTreeMap<(usize, usize), usize>,
+}
+
+impl Distributions {
+    pub fn collect(time: f32, slimes: &Population) -> Self {
+        let ages = slimes
+            .born
+            .iter()
+            .map(|born| time - born)
+            .collect::<Vec<_>>();
+        let mut vision_efficiency = BTreeMap::new();
+        for skills in slimes.skills.iter() {
+            *vision_efficiency
+                .entry((skills.vision, skills.efficiency))
+                .or_insert(0) += 1;
+        }
+        Self {
+            time,
+            energy: Histogram::new(&slimes.energy, BINS),
+            size: Histogram::new(&slimes.size, BINS),
+            age: Histogram::new(&ages, BINS),
+            vision: Histogram::levels(slimes.skills.iter().map(|s| s.vision)),
+            efficiency: Histogram::levels(slimes.skills.iter().map(|s| s.efficiency)),
+            jumper: Histogram::levels(slimes.skills.iter().map(|s| s.jumper)),
+            vision_efficiency,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_works() {
+        let histogram = Histogram::new(&[0.0, 1.0, 2.5, 10.0], 4);
+        assert_eq!((histogram.min, histogram.max), (0.0, 10.0));
+        assert_eq!(histogram.counts, vec![2, 1, 0, 1]);
+        assert_eq!(Histogram::new(&[], 4).counts, Vec::<usize>::new());
+        assert_eq!(Histogram::new(&[3.0, 3.0], 4).counts, vec![2, 0, 0, 0]);
+        let levels = Histogram::levels([0, 2, 2].into_iter());
+        assert_eq!(levels.counts, vec![1, 0, 2]);
+    }
+}
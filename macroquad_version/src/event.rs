@@ -0,0 +1,165 @@
+//! # Events.
+//!
+//! Disasters and interventions that can be scheduled or triggered from the UI.
+use crate::{rng::gen_range, utils::serde_vec2};
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// No food spawns for `duration` time steps.
+    Famine { duration: f32 },
+    /// Kills every slime touching the circle, at a random position if none is given.
+    Meteor {
+        #[serde(default, with = "serde_vec2::option")]
+        position: Option<Vec2>,
+        radius: f32,
+    },
+    /// Spawns `amount` food at once (ignoring the food limit).
+    Bloom { amount: usize },
+    /// Enables the disease and infects `infected` random slimes.
+    Plague { infected: usize },
+    /// Spawns `amount` slimes with the given (vision, efficiency, jumper) skill levels.
+    Migration {
+        amount: usize,
+        #[serde(default)]
+        skills: (usize, usize, usize),
+    },
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Famine { .. } => "Famine",
+            Self::Meteor { .. } => "Meteor",
+            Self::Bloom { .. } => "Bloom",
+            Self::Plague { .. } => "Plague",
+            Self::Migration { .. } => "Migration",
+        }
+    }
+}
+
+/// An event with the rule of when it happens:
+/// - `time`: once at the given time step.
+/// - `every`: periodically every given time steps.
+/// - `chance`: on each time step with the given probability.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScheduledEvent {
+    #[serde(default)]
+    pub time: Option<f32>,
+    #[serde(default)]
+    pub every: Option<f32>,
+    #[serde(default)]
+    pub chance: Option<f32>,
+    #[serde(flatten)]
+    pub event: Event,
+    #[serde(skip)]
+    last_time: Option<f32>,
+}
+
+impl ScheduledEvent {
+    fn is_due(&self, time: f32) -> bool {
+        if let Some(at) = self.time {
+            if self.last_time.is_none() && (time >= at) {
+                return true;
+            }
+        }
+        if let Some(every) = self.every {
+            let last = self.last_time.unwrap_or(0.0);
+            if (time - last) >= every {
+                return true;
+            }
+        }
+        if let Some(chance) = self.chance {
+            if gen_range(0.0, 1.0) < chance {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EventSchedule {
+    #[serde(default)]
+    pub events: Vec<ScheduledEvent>,
+}
+
+impl EventSchedule {
+    /// Loads the schedule from a TOML file:
+    /// ```toml
+    /// [[events]]
+    /// time = 5000.0
+    /// type = "meteor"
+    /// radius = 80.0
+    ///
+    /// [[events]]
+    /// chance = 0.0001
+    /// type = "bloom"
+    /// amount = 50
+    /// ```
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        toml::from_str(&content).map_err(|e| e.to_string())
+    }
+
+    /// Returns the events happening at `time`.
+    pub fn check_events(&mut self, time: f32) -> Vec<Event> {
+        self.events
+            .iter_mut()
+            .filter_map(|e| {
+                if e.is_due(time) {
+                    e.last_time = Some(time);
+                    Some(e.event.clone())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Allows all events to happen again.
+    pub fn reset(&mut self) {
+        self.events.iter_mut().for_each(|e| e.last_time = None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule_works() {
+        let mut schedule: EventSchedule = toml::from_str(
+            r#"
+            [[events]]
+            time = 5.0
+            type = "meteor"
+            radius = 80.0
+
+            [[events]]
+            every = 4.0
+            type = "migration"
+            amount = 3
+            skills = [2, 0, 1]
+            "#,
+        )
+        .unwrap();
+        let events = (0..10)
+            .map(|t| schedule.check_events(t as f32))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events[5],
+            vec![Event::Meteor {
+                position: None,
+                radius: 80.0
+            }]
+        );
+        let migrations = events.iter().flatten().filter(|e| e.name() == "Migration");
+        assert_eq!(migrations.count(), 2);
+        schedule.reset();
+        assert_eq!(schedule.check_events(5.0).len(), 2);
+    }
+}
@@ -1,12 +1,20 @@
 //! # Food entity.
 #![doc = include_str!("../../docs/food.md")]
-use crate::utils::{random_screen_position, wrap_around};
+use crate::{
+    biome::BiomeMap,
+    obstacle::ObstacleMap,
+    rng::gen_range,
+    utils::{random_position, wrap_around},
+};
 use macroquad::prelude::*;
-use macroquad::rand::gen_range;
 use std::f32::consts::PI;
 
 pub const FOOD_SIZE: f32 = 3.0;
+/// Maximum number of random positions tried to spawn food outside obstacles
+/// and according to biomes spawn rates.
+pub(crate) const SPAWN_ATTEMPTS: usize = 20;
 
+#[derive(Clone)]
 pub struct Food {
     pub position: Vec2,
     pub energy: f32,
@@ -15,17 +23,17 @@ pub struct Food {
 }
 
 impl Food {
-    pub fn spawn(energy_range: (f32, f32), speed_range: (f32, f32)) -> Self {
+    pub fn spawn(position: Vec2, energy_range: (f32, f32), speed_range: (f32, f32)) -> Self {
         let energy = gen_range(energy_range.0, energy_range.1);
         // Get speed as proportional to energy
         let speed_factor = speed_range.0
-            + ((energy - energy_range.0) as f32 / (energy_range.1 - energy_range.0) as f32
+            + ((energy - energy_range.0) / (energy_range.1 - energy_range.0)
                 * (speed_range.1 - speed_range.0));
         // Get random direction angle
         let direction = gen_range(0.0, PI * 2.0);
         let speed = polar_to_cartesian(speed_factor, direction);
         Self {
-            position: random_screen_position(),
+            position,
             energy,
             _speed_factor: speed_factor,
             speed,
@@ -33,7 +41,9 @@ impl Food {
     }
 }
 
+#[derive(Clone)]
 pub struct FoodController {
+    pub world_size: Vec2,
     /// Spawn time
     pub spawn_time: f32,
     /// Maximum number of food instances that can exist at the same time.
@@ -42,23 +52,28 @@ pub struct FoodController {
     pub speed_range: (f32, f32),
     time: f32,
     pub last_spawn_time: f32,
+    /// No food spawns until this time.
+    pub famine_until: f32,
     pub population: Vec<Food>,
 }
 
 impl FoodController {
     pub fn new(
+        world_size: Vec2,
         spawn_time: f32,
         limit: f32,
         energy_range: (f32, f32),
         speed_range: (f32, f32),
     ) -> Self {
         Self {
+            world_size,
             spawn_time,
             limit,
             energy_range,
             speed_range,
             time: 0.0,
             last_spawn_time: 0.0,
+            famine_until: 0.0,
             population: Vec::with_capacity(limit as usize),
         }
     }
@@ -72,34 +87,52 @@ impl FoodController {
         self.time = time;
     }
 
-    pub fn spawn_one(&mut self) {
+    /// Spawns one food instance at a random position outside the obstacles, biomes
+    /// with higher spawn rates are more likely to be chosen.
+    pub fn spawn_one(&mut self, obstacles: &ObstacleMap, biomes: &BiomeMap) {
+        let position = (0..SPAWN_ATTEMPTS)
+            .map(|_| random_position(self.world_size))
+            .find(|&p| !obstacles.contains(p, FOOD_SIZE) && biomes.accept_food_spawn(p));
+        if let Some(position) = position {
+            self.population
+                .push(Food::spawn(position, self.energy_range, self.speed_range));
+        }
+    }
+
+    /// Spawns one food instance at `position`.
+    pub fn spawn_at(&mut self, position: Vec2) {
         self.population
-            .push(Food::spawn(self.energy_range, self.speed_range));
+            .push(Food::spawn(position, self.energy_range, self.speed_range));
     }
 
-    pub fn spawn_n(&mut self, n: usize) {
+    pub fn spawn_n(&mut self, n: usize, obstacles: &ObstacleMap, biomes: &BiomeMap) {
         let n = self.limit().saturating_sub(self.population.len()).min(n);
-        (0..n).for_each(|_| self.spawn_one())
+        (0..n).for_each(|_| self.spawn_one(obstacles, biomes))
+    }
+
+    /// Stops spawning food for `duration` time steps.
+    pub fn start_famine(&mut self, duration: f32) {
+        self.famine_until = self.time + duration;
     }
 
     /// Check timer to spawn one food instance.
-    pub fn check_spawn(&mut self) {
+    pub fn check_spawn(&mut self, obstacles: &ObstacleMap, biomes: &BiomeMap) {
         if (self.time - self.last_spawn_time) >= self.spawn_time {
-            if self.limit() > self.population.len() {
-                self.spawn_one();
+            if (self.limit() > self.population.len()) && (self.time >= self.famine_until) {
+                self.spawn_one(obstacles, biomes);
             }
             self.last_spawn_time = self.time;
         }
     }
 
-    /// 1. Update all food positions.
+    /// 1. Update all food positions, bouncing against obstacles.
     /// 2. Check to spawn more food.
-    pub fn update_step(&mut self) {
+    pub fn update_step(&mut self, obstacles: &ObstacleMap, biomes: &BiomeMap) {
         for food in self.population.iter_mut() {
-            food.position += food.speed;
-            food.position = wrap_around(&food.position);
+            food.position = obstacles.resolve(food.position, FOOD_SIZE, &mut food.speed);
+            food.position = wrap_around(&food.position, self.world_size);
         }
-        self.check_spawn();
+        self.check_spawn(obstacles, biomes);
     }
 }
 
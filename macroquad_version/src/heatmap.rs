@@ -0,0 +1,217 @@
+//! # Heatmaps.
+//!
+//! Grids counting where slimes spend their time, eat, die and are born over the last
+//! time steps. The window is split in periods and the oldest one is dropped as a whole,
+//! so each step only adds to the newest grid.
+use macroquad::prelude::*;
+use std::{collections::VecDeque, path::Path};
+
+/// Side of the heatmap cells.
+pub const HEATMAP_CELL_SIZE: f32 = 10.0;
+/// Number of periods in the window.
+const PERIODS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeatmapKind {
+    Presence,
+    FoodEaten,
+    Deaths,
+    Births,
+}
+
+impl HeatmapKind {
+    pub const ALL: [HeatmapKind; 4] = [
+        HeatmapKind::Presence,
+        HeatmapKind::FoodEaten,
+        HeatmapKind::Deaths,
+        HeatmapKind::Births,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Presence => "Presence",
+            Self::FoodEaten => "Food eaten",
+            Self::Deaths => "Deaths",
+            Self::Births => "Births",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Self::Presence => SKYBLUE,
+            Self::FoodEaten => GREEN,
+            Self::Deaths => RED,
+            Self::Births => PINK,
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// Positions where slimes ate, died and were born since it was cleared.
+#[derive(Clone, Debug, Default)]
+pub struct Activity {
+    pub eaten: Vec<Vec2>,
+    pub deaths: Vec<Vec2>,
+    pub births: Vec<Vec2>,
+}
+
+impl Activity {
+    pub fn clear(&mut self) {
+        self.eaten.clear();
+        self.deaths.clear();
+        self.births.clear();
+    }
+}
+
+#[derive(Clone)]
+pub struct Heatmaps {
+    /// Count every step, disabled the heatmaps stay empty.
+    pub enabled: bool,
+    /// Time steps counted.
+    pub window: f32,
+    /// Start time and grid of each kind for every period, the newest at the back.
+    periods: VecDeque<(f32, [Vec<f32>; 4])>,
+    cols: usize,
+    rows: usize,
+}
+
+impl Heatmaps {
+    pub fn new(world_size: Vec2) -> Self {
+        Self {
+            enabled: false,
+            window: 5000.0,
+            periods: VecDeque::with_capacity(PERIODS + 1),
+            cols: (world_size.x / HEATMAP_CELL_SIZE).ceil().max(1.0) as usize,
+            rows: (world_size.y / HEATMAP_CELL_SIZE).ceil().max(1.0) as usize,
+        }
+    }
+
+    /// Index of the cell at `position`, positions outside the grid use the closest cell.
+    fn cell(&self, position: Vec2) -> usize {
+        let col = ((position.x / HEATMAP_CELL_SIZE).max(0.0) as usize).min(self.cols - 1);
+        let row = ((position.y / HEATMAP_CELL_SIZE).max(0.0) as usize).min(self.rows - 1);
+        row * self.cols + col
+    }
+
+    /// Counts the slimes at `positions` and the activity of the step at `time`.
+    pub fn record(&mut self, time: f32, positions: &[Vec2], activity: &Activity) {
+        if !self.enabled {
+            return;
+        }
+        // The simulation may have gone back in time
+        if self.periods.back().is_some_and(|(start, _)| time < *start) {
+            self.clear();
+        }
+        let period = self.window / PERIODS as f32;
+        if self
+            .periods
+            .back()
+            .is_none_or(|(start, _)| (time - start) >= period)
+        {
+            let empty = vec![0.0; self.cols * self.rows];
+            self.periods
+                .push_back((time, [0; 4].map(|_| empty.clone())));
+        }
+        while self.periods.len() > PERIODS {
+            self.periods.pop_front();
+        }
+        let cells = [
+            positions,
+            activity.eaten.as_slice(),
+            activity.deaths.as_slice(),
+            activity.births.as_slice(),
+        ]
+        .map(|positions| positions.iter().map(|&p| self.cell(p)).collect::<Vec<_>>());
+        let (_, grids) = self.periods.back_mut().unwrap();
+        for (grid, cells) in grids.iter_mut().zip(cells) {
+            cells.into_iter().for_each(|cell| grid[cell] += 1.0);
+        }
+    }
+
+    /// Counts of `kind` for each cell over the window.
+    pub fn counts(&self, kind: HeatmapKind) -> Vec<f32> {
+        let mut counts = vec![0.0; self.cols * self.rows];
+        for (_, grids) in self.periods.iter() {
+            counts
+                .iter_mut()
+                .zip(grids[kind.index()].iter())
+                .for_each(|(count, value)| *count += value);
+        }
+        counts
+    }
+
+    pub fn clear(&mut self) {
+        self.periods.clear();
+    }
+
+    /// Opacity of each cell, relative to the highest count.
+    fn intensities(&self, kind: HeatmapKind) -> Vec<f32> {
+        let counts = self.counts(kind);
+        let max = counts.iter().cloned().fold(0.0, f32::max).max(1.0);
+        counts.into_iter().map(|count| count / max).collect()
+    }
+
+    pub fn draw(&self, kind: HeatmapKind) {
+        let color = kind.color();
+        for (i, intensity) in self.intensities(kind).into_iter().enumerate() {
+            if intensity > 0.0 {
+                draw_rectangle(
+                    (i % self.cols) as f32 * HEATMAP_CELL_SIZE,
+                    (i / self.cols) as f32 * HEATMAP_CELL_SIZE,
+                    HEATMAP_CELL_SIZE,
+                    HEATMAP_CELL_SIZE,
+                    Color::new(color.r, color.g, color.b, intensity * 0.7),
+                );
+            }
+        }
+    }
+
+    /// Saves the heatmap as a PNG image with a pixel per cell, from black to the color
+    /// of `kind`.
+    pub fn export_png(&self, kind: HeatmapKind, path: impl AsRef<Path>) -> Result<(), String> {
+        let color = kind.color();
+        let intensities = self.intensities(kind);
+        let image = image::RgbImage::from_fn(self.cols as u32, self.rows as u32, |x, y| {
+            let intensity = intensities[y as usize * self.cols + x as usize];
+            image::Rgb([color.r, color.g, color.b].map(|c| (c * intensity * 255.0) as u8))
+        });
+        image.save(path).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_works() {
+        let mut heatmaps = Heatmaps::new(vec2(100.0, 100.0));
+        heatmaps.enabled = true;
+        heatmaps.window = 100.0;
+        let activity = Activity {
+            deaths: vec![vec2(55.0, 5.0)],
+            ..Activity::default()
+        };
+        for time in 0..50 {
+            heatmaps.record(time as f32, &[vec2(5.0, 15.0)], &activity);
+        }
+        assert_eq!(heatmaps.counts(HeatmapKind::Presence)[10], 50.0);
+        assert_eq!(heatmaps.counts(HeatmapKind::Deaths)[5], 50.0);
+        assert_eq!(
+            heatmaps.counts(HeatmapKind::Births).iter().sum::<f32>(),
+            0.0
+        );
+        // Only the last periods are kept
+        for time in 50..200 {
+            heatmaps.record(time as f32, &[vec2(5.0, 15.0)], &Activity::default());
+        }
+        assert_eq!(heatmaps.counts(HeatmapKind::Presence)[10], 100.0);
+        assert_eq!(heatmaps.counts(HeatmapKind::Deaths)[5], 0.0);
+        // Going back in time starts over
+        heatmaps.record(0.0, &[], &Activity::default());
+        assert_eq!(heatmaps.counts(HeatmapKind::Presence)[10], 0.0);
+    }
+}
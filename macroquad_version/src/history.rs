@@ -0,0 +1,92 @@
+//! # Rewind history.
+//!
+//! Periodic snapshots of the simulation, so the world can go back some steps and run
+//! them again.
+use crate::simulation::{Simulation, Snapshot};
+use std::collections::VecDeque;
+
+pub struct History {
+    /// How often (time steps) a snapshot is taken.
+    pub interval: f32,
+    /// Maximum number of snapshots kept, older ones are dropped.
+    pub capacity: usize,
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl History {
+    pub fn new(interval: f32, capacity: usize) -> Self {
+        Self {
+            interval,
+            capacity,
+            snapshots: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Takes a snapshot if `interval` steps passed since the last one.
+    pub fn check_snapshot(&mut self, simulation: &Simulation) {
+        if self
+            .snapshots
+            .back()
+            .is_some_and(|s| (simulation.time - s.time) < self.interval)
+        {
+            return;
+        }
+        if self.snapshots.len() >= self.capacity.max(1) {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(simulation.snapshot());
+    }
+
+    /// Restores the latest snapshot at least `steps` before the current time (or the
+    /// oldest one), dropping the newer snapshots. Returns false if there are none.
+    pub fn rewind(&mut self, simulation: &mut Simulation, steps: f32) -> bool {
+        let target = simulation.time - steps.max(1.0);
+        while self.snapshots.len() > 1 && self.snapshots.back().unwrap().time > target {
+            self.snapshots.pop_back();
+        }
+        match self.snapshots.back() {
+            Some(snapshot) if snapshot.time < simulation.time => {
+                simulation.restore(snapshot);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Time of the oldest snapshot.
+    pub fn oldest_time(&self) -> f32 {
+        self.snapshots.front().map_or(0.0, |s| s.time)
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scenario::Scenario, simulation::DEFAULT_WORLD_SIZE};
+
+    #[test]
+    fn rewind_works() {
+        let mut simulation = Simulation::new(Scenario::default(), DEFAULT_WORLD_SIZE).unwrap();
+        let mut history = History::new(10.0, 5);
+        for _ in 0..100 {
+            history.check_snapshot(&simulation);
+            simulation.step();
+        }
+        assert_eq!(history.snapshots.len(), 5);
+        assert_eq!(history.oldest_time(), 50.0);
+        assert!(history.rewind(&mut simulation, 25.0));
+        assert_eq!(simulation.time, 70.0);
+        // The restored snapshot is kept to rewind to it again
+        simulation.step();
+        assert!(history.rewind(&mut simulation, 1.0));
+        assert_eq!(simulation.time, 70.0);
+        // Too far back goes to the oldest snapshot
+        assert!(history.rewind(&mut simulation, 1000.0));
+        assert_eq!(simulation.time, 50.0);
+        assert!(!history.rewind(&mut simulation, 1000.0));
+    }
+}
@@ -8,8 +8,30 @@
 //! ## Slime
 #![doc = include_str!("../../docs/slime.md")]
 
+pub mod biome;
+pub mod camera;
+pub mod charts;
+pub mod clock;
+pub mod coloring;
+pub mod disease;
+pub mod distribution;
+pub mod event;
 pub mod food;
+pub mod heatmap;
+pub mod history;
+pub mod obstacle;
+pub mod optimizer;
+pub mod population;
+pub mod render;
+pub mod replay;
+pub mod rng;
+pub mod scenario;
+pub mod scent;
+pub mod simulation;
 pub mod slime;
+pub mod stats;
+pub mod sweep;
+pub mod trail;
 pub mod utils;
 pub mod world;
 
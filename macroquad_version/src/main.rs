@@ -1,7 +1,46 @@
-use evolution_simulation::World;
+use evolution_simulation::{
+    biome::BiomeMap, event::EventSchedule, obstacle::ObstacleMap, scenario::Scenario, World,
+};
 
 #[macroquad::main("Evolution simulation")]
 async fn main() {
-    let world = World::new(20, 4);
+    let mut world = World::new(20, 4);
+    // Usage: evolution_simulation [--scenario <file.toml>] [--obstacles <file.toml>]
+    //                             [--biomes <image.png>] [--events <file.toml>]
+    //                             [--replay <file.toml>]
+    let args = std::env::args().collect::<Vec<_>>();
+    let arg_value = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+    };
+    if let Some(path) = arg_value("--scenario") {
+        if let Err(e) = Scenario::load(path).and_then(|scenario| world.load_scenario(scenario)) {
+            eprintln!("Failed to load scenario from {}: {}", path, e);
+        }
+    }
+    if let Some(path) = arg_value("--obstacles") {
+        match ObstacleMap::load(path) {
+            Ok(obstacles) => world = world.with_obstacles(obstacles),
+            Err(e) => eprintln!("Failed to load obstacles from {}: {}", path, e),
+        }
+    }
+    if let Some(path) = arg_value("--biomes") {
+        match BiomeMap::load(path, world.world_size()) {
+            Ok(biomes) => world = world.with_biomes(biomes),
+            Err(e) => eprintln!("Failed to load biomes from {}: {}", path, e),
+        }
+    }
+    if let Some(path) = arg_value("--events") {
+        match EventSchedule::load(path) {
+            Ok(events) => world = world.with_events(events),
+            Err(e) => eprintln!("Failed to load events from {}: {}", path, e),
+        }
+    }
+    if let Some(path) = arg_value("--replay") {
+        if let Err(e) = world.play_replay(path) {
+            eprintln!("Failed to play replay from {}: {}", path, e);
+        }
+    }
     world.run().await;
 }
@@ -0,0 +1,340 @@
+//! # Obstacle entity.
+//!
+//! Static shapes that block the movement and vision of slimes and food.
+use crate::utils::serde_vec2;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// Thickness of the walls made by polylines.
+pub const WALL_THICKNESS: f32 = 3.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Obstacle {
+    /// Axis aligned rectangle with its top left corner at `position`.
+    Rectangle {
+        #[serde(with = "serde_vec2")]
+        position: Vec2,
+        #[serde(with = "serde_vec2")]
+        size: Vec2,
+    },
+    Circle {
+        #[serde(with = "serde_vec2")]
+        center: Vec2,
+        radius: f32,
+    },
+    /// Connected walls of `WALL_THICKNESS` width.
+    Polyline {
+        #[serde(with = "serde_vec2::vec")]
+        points: Vec<Vec2>,
+    },
+}
+
+impl Obstacle {
+    /// Returns if a circle of `radius` centered at `point` touches the obstacle.
+    pub fn contains(&self, point: Vec2, radius: f32) -> bool {
+        self.collide(point, radius).is_some()
+    }
+
+    /// Checks if a circle of `radius` centered at `position` overlaps the obstacle and
+    /// returns the normal to push it out and the penetration depth.
+    pub fn collide(&self, position: Vec2, radius: f32) -> Option<(Vec2, f32)> {
+        match self {
+            Self::Rectangle {
+                position: min,
+                size,
+            } => {
+                let max = *min + *size;
+                let closest = position.clamp(*min, max);
+                if closest != position {
+                    let diff = position - closest;
+                    let distance = diff.length();
+                    (distance < radius).then(|| (diff / distance, radius - distance))
+                } else {
+                    // Center is inside: push out through the closest edge
+                    [
+                        (-Vec2::X, position.x - min.x),
+                        (Vec2::X, max.x - position.x),
+                        (-Vec2::Y, position.y - min.y),
+                        (Vec2::Y, max.y - position.y),
+                    ]
+                    .into_iter()
+                    .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                    .map(|(normal, distance)| (normal, distance + radius))
+                }
+            }
+            Self::Circle { center, radius: r } => circle_collide(position, radius, *center, *r),
+            Self::Polyline { points } => points
+                .windows(2)
+                .filter_map(|w| {
+                    let closest = closest_point_on_segment(position, w[0], w[1]);
+                    circle_collide(position, radius, closest, WALL_THICKNESS / 2.0)
+                })
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap()),
+        }
+    }
+
+    /// Returns if the segment from `a` to `b` crosses the obstacle.
+    pub fn blocks(&self, a: Vec2, b: Vec2) -> bool {
+        match self {
+            Self::Rectangle { position, size } => segment_intersects_rect(a, b, *position, *size),
+            Self::Circle { center, radius } => {
+                closest_point_on_segment(*center, a, b).distance(*center) <= *radius
+            }
+            Self::Polyline { points } => points
+                .windows(2)
+                .any(|w| segments_intersect(a, b, w[0], w[1])),
+        }
+    }
+
+    pub fn draw(&self, color: Color) {
+        match self {
+            Self::Rectangle { position, size } => {
+                draw_rectangle(position.x, position.y, size.x, size.y, color)
+            }
+            Self::Circle { center, radius } => draw_circle(center.x, center.y, *radius, color),
+            Self::Polyline { points } => points.windows(2).for_each(|w| {
+                draw_line(w[0].x, w[0].y, w[1].x, w[1].y, WALL_THICKNESS, color);
+                draw_circle(w[1].x, w[1].y, WALL_THICKNESS / 2.0, color);
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ObstacleMap {
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+}
+
+impl ObstacleMap {
+    /// Loads obstacles from a TOML file:
+    /// ```toml
+    /// [[obstacles]]
+    /// type = "circle"
+    /// center = [200.0, 150.0]
+    /// radius = 40.0
+    /// ```
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        toml::from_str(&content).map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let content = toml::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(path, content).map_err(|e| e.to_string())
+    }
+
+    pub fn push(&mut self, obstacle: Obstacle) {
+        self.obstacles.push(obstacle);
+    }
+
+    pub fn clear(&mut self) {
+        self.obstacles.clear();
+    }
+
+    /// Returns if a circle of `radius` centered at `point` touches any obstacle.
+    pub fn contains(&self, point: Vec2, radius: f32) -> bool {
+        self.obstacles.iter().any(|o| o.contains(point, radius))
+    }
+
+    /// Returns if there is a clear line of sight from `a` to `b`.
+    pub fn is_visible(&self, a: Vec2, b: Vec2) -> bool {
+        !self.obstacles.iter().any(|o| o.blocks(a, b))
+    }
+
+    /// Moves a circle of `radius` centered at `position` by `speed`, pushing it out of
+    /// the obstacles and bouncing its `speed` against them. Returns the new position.
+    ///
+    /// Long moves are checked in steps no longer than the radius, so fast circles stop
+    /// at thin walls instead of jumping over them.
+    pub fn resolve(&self, position: Vec2, radius: f32, speed: &mut Vec2) -> Vec2 {
+        let target = position + *speed;
+        let steps = (speed.length() / radius.max(WALL_THICKNESS / 2.0))
+            .ceil()
+            .max(1.0);
+        for step in 1..=steps as usize {
+            let position = position.lerp(target, step as f32 / steps);
+            let resolved = self.push_out(position, radius, speed);
+            if resolved != position {
+                return resolved;
+            }
+        }
+        target
+    }
+
+    fn push_out(&self, mut position: Vec2, radius: f32, speed: &mut Vec2) -> Vec2 {
+        for obstacle in self.obstacles.iter() {
+            if let Some((normal, depth)) = obstacle.collide(position, radius) {
+                position += normal * depth;
+                let towards = speed.dot(normal);
+                if towards < 0.0 {
+                    *speed -= 2.0 * towards * normal;
+                }
+            }
+        }
+        position
+    }
+}
+
+fn circle_collide(position: Vec2, radius: f32, center: Vec2, r: f32) -> Option<(Vec2, f32)> {
+    let diff = position - center;
+    let distance = diff.length();
+    let depth = radius + r - distance;
+    if depth <= 0.0 {
+        None
+    } else if distance > 0.0 {
+        Some((diff / distance, depth))
+    } else {
+        Some((Vec2::X, depth))
+    }
+}
+
+fn closest_point_on_segment(point: Vec2, a: Vec2, b: Vec2) -> Vec2 {
+    let ab = b - a;
+    let length_squared = ab.length_squared();
+    if length_squared == 0.0 {
+        return a;
+    }
+    let t = ((point - a).dot(ab) / length_squared).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+fn segments_intersect(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    let ab = b - a;
+    let cd = d - c;
+    let denominator = ab.perp_dot(cd);
+    if denominator == 0.0 {
+        return false;
+    }
+    let t = (c - a).perp_dot(cd) / denominator;
+    let u = (c - a).perp_dot(ab) / denominator;
+    (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)
+}
+
+/// Slab test between the segment `a` to `b` and the rectangle.
+fn segment_intersects_rect(a: Vec2, b: Vec2, position: Vec2, size: Vec2) -> bool {
+    let direction = b - a;
+    let (mut t_min, mut t_max) = (0.0_f32, 1.0_f32);
+    for (origin, direction, min, max) in [
+        (a.x, direction.x, position.x, position.x + size.x),
+        (a.y, direction.y, position.y, position.y + size.y),
+    ] {
+        if direction.abs() < f32::EPSILON {
+            if origin < min || origin > max {
+                return false;
+            }
+        } else {
+            let t1 = (min - origin) / direction;
+            let t2 = (max - origin) / direction;
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+            if t_min > t_max {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collide_works() {
+        let rectangle = Obstacle::Rectangle {
+            position: vec2(0.0, 0.0),
+            size: vec2(10.0, 10.0),
+        };
+        assert!(rectangle.collide(vec2(-5.0, 5.0), 2.0).is_none());
+        let (normal, depth) = rectangle.collide(vec2(-1.0, 5.0), 2.0).unwrap();
+        assert_eq!(normal, -Vec2::X);
+        assert!((depth - 1.0).abs() < 1e-5);
+        let (normal, _) = rectangle.collide(vec2(5.0, 9.0), 0.0).unwrap();
+        assert_eq!(normal, Vec2::Y);
+        let circle = Obstacle::Circle {
+            center: vec2(0.0, 0.0),
+            radius: 5.0,
+        };
+        assert!(circle.contains(vec2(6.0, 0.0), 1.5));
+        assert!(!circle.contains(vec2(7.0, 0.0), 1.5));
+    }
+
+    #[test]
+    fn blocks_works() {
+        let obstacles = [
+            Obstacle::Rectangle {
+                position: vec2(4.0, -1.0),
+                size: vec2(2.0, 2.0),
+            },
+            Obstacle::Circle {
+                center: vec2(5.0, 0.0),
+                radius: 1.0,
+            },
+            Obstacle::Polyline {
+                points: vec![vec2(5.0, -5.0), vec2(5.0, 5.0)],
+            },
+        ];
+        for obstacle in obstacles {
+            assert!(
+                obstacle.blocks(vec2(0.0, 0.0), vec2(10.0, 0.0)),
+                "{:?}",
+                obstacle
+            );
+            assert!(
+                !obstacle.blocks(vec2(0.0, 8.0), vec2(10.0, 8.0)),
+                "{:?}",
+                obstacle
+            );
+        }
+    }
+
+    #[test]
+    fn resolve_stops_fast_moves_at_thin_walls() {
+        let walls = [
+            Obstacle::Polyline {
+                points: vec![vec2(10.0, -20.0), vec2(10.0, 20.0)],
+            },
+            Obstacle::Rectangle {
+                position: vec2(9.5, -20.0),
+                size: vec2(1.0, 20.0),
+            },
+        ];
+        for wall in walls {
+            let map = ObstacleMap {
+                obstacles: vec![wall],
+            };
+            // A slime moving far enough in one step to land past the wall
+            let mut speed = vec2(10.0, 0.0);
+            let position = map.resolve(vec2(5.0, -1.0), 1.5, &mut speed);
+            assert!(position.x < 10.0, "{:?}", map.obstacles);
+            assert!(speed.x < 0.0, "{:?}", map.obstacles);
+        }
+        // Nothing in the way
+        let mut speed = vec2(10.0, 0.0);
+        let position = ObstacleMap::default().resolve(vec2(5.0, -1.0), 1.5, &mut speed);
+        assert_eq!(position, vec2(15.0, -1.0));
+        assert_eq!(speed, vec2(10.0, 0.0));
+    }
+
+    #[test]
+    fn load_works() {
+        let map: ObstacleMap = toml::from_str(
+            r#"
+            [[obstacles]]
+            type = "rectangle"
+            position = [10.0, 10.0]
+            size = [50.0, 20.0]
+
+            [[obstacles]]
+            type = "polyline"
+            points = [[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(map.obstacles.len(), 2);
+        assert!(!map.is_visible(vec2(0.0, 20.0), vec2(100.0, 20.0)));
+    }
+}
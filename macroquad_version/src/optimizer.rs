@@ -0,0 +1,305 @@
+//! # Optimizer.
+//!
+//! Genetic algorithm that searches scenario fields (within the given ranges) for the
+//! values that best meet an objective, running the candidates headless in parallel.
+use crate::{
+    population::Population,
+    rng,
+    scenario::Scenario,
+    simulation::{Simulation, DEFAULT_WORLD_SIZE},
+    slime::SkillType,
+};
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use toml::Value;
+
+/// Candidates compared in each tournament selection.
+const TOURNAMENT_SIZE: usize = 3;
+
+/// What a good world looks like, checked on every time step.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Objective {
+    /// The population stays between `min` and `max`.
+    PopulationRange { min: usize, max: usize },
+    /// Every skill path is followed by at least `min_count` slimes.
+    Coexistence {
+        #[serde(default = "default_min_count")]
+        min_count: usize,
+    },
+}
+
+fn default_min_count() -> usize {
+    1
+}
+
+impl Objective {
+    fn is_met(&self, slimes: &Population) -> bool {
+        match self {
+            Self::PopulationRange { min, max } => (*min..=*max).contains(&slimes.len()),
+            Self::Coexistence { min_count } => SkillType::ALL.iter().all(|&skill| {
+                slimes.skill_path.iter().filter(|&&s| s == skill).count() >= *min_count
+            }),
+        }
+    }
+}
+
+/// Scenario field searched in the range [min, max].
+#[derive(Clone, Debug, Deserialize)]
+pub struct Param {
+    pub field: String,
+    pub range: (f64, f64),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Optimizer {
+    /// Base scenario file, relative to the optimizer file. The default scenario is used
+    /// when missing.
+    #[serde(default)]
+    pub scenario: Option<PathBuf>,
+    /// Each candidate runs once with each seed, its fitness is the average.
+    #[serde(default = "default_seeds")]
+    pub seeds: Vec<u64>,
+    /// Time steps of each run.
+    pub max_steps: f32,
+    pub generations: usize,
+    /// Candidates per generation.
+    pub population: usize,
+    /// Best candidates kept unchanged in the next generation.
+    #[serde(default)]
+    pub elite: usize,
+    /// Maximum change of a mutated value, as a fraction of its range.
+    #[serde(default = "default_mutation")]
+    pub mutation: f64,
+    /// Seed used for the search (not for the runs).
+    #[serde(default)]
+    pub seed: u64,
+    pub objective: Objective,
+    pub params: Vec<Param>,
+}
+
+fn default_seeds() -> Vec<u64> {
+    vec![0]
+}
+
+fn default_mutation() -> f64 {
+    0.1
+}
+
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    /// Value of each param.
+    pub values: Vec<f64>,
+    /// Fraction of the time steps meeting the objective, averaged over the seeds.
+    pub fitness: f64,
+}
+
+impl Optimizer {
+    /// Loads an optimizer from a TOML file, see `sweeps/optimizer_example.toml`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut optimizer: Self = toml::from_str(&content).map_err(|e| e.to_string())?;
+        if optimizer.population == 0 {
+            return Err("Population must be at least 1".to_string());
+        }
+        if let (Some(scenario), Some(dir)) = (&optimizer.scenario, path.parent()) {
+            optimizer.scenario = Some(dir.join(scenario));
+        }
+        Ok(optimizer)
+    }
+
+    pub fn base_scenario(&self) -> Result<Scenario, String> {
+        match &self.scenario {
+            Some(path) => Scenario::load(path),
+            None => Ok(Scenario::default()),
+        }
+    }
+
+    /// Returns the base scenario with the candidate values.
+    pub fn candidate_scenario(&self, base: &Scenario, values: &[f64]) -> Result<Scenario, String> {
+        let fields = self
+            .params
+            .iter()
+            .zip(values)
+            .map(|(p, &v)| (p.field.clone(), Value::Float(v)))
+            .collect::<Vec<_>>();
+        base.with_fields(&fields)
+    }
+
+    fn random_values(&self) -> Vec<f64> {
+        self.params
+            .iter()
+            .map(|p| rng::gen_range(p.range.0, p.range.1))
+            .collect()
+    }
+
+    /// Picks the best of `TOURNAMENT_SIZE` random candidates.
+    fn select<'a>(&self, candidates: &'a [Candidate]) -> &'a Candidate {
+        (0..TOURNAMENT_SIZE)
+            .map(|_| &candidates[rng::gen_range(0, candidates.len())])
+            .max_by(|a, b| a.fitness.total_cmp(&b.fitness))
+            .unwrap()
+    }
+
+    /// Uniform crossover of two parents followed by a random mutation of each value.
+    fn breed(&self, a: &Candidate, b: &Candidate) -> Vec<f64> {
+        self.params
+            .iter()
+            .zip(a.values.iter().zip(b.values.iter()))
+            .map(|(p, (&a, &b))| {
+                let value = if rng::gen_range(0, 2) == 0 { a } else { b };
+                let (min, max) = p.range;
+                let change = (max - min) * self.mutation;
+                (value + rng::gen_range(-change, change)).clamp(min, max)
+            })
+            .collect()
+    }
+
+    /// Runs the candidate once per seed and returns its fitness.
+    fn evaluate(&self, base: &Scenario, values: &[f64]) -> Result<f64, String> {
+        let mut scenario = self.candidate_scenario(base, values)?;
+        scenario.stop.max_steps = Some(self.max_steps);
+        let fitness = self
+            .seeds
+            .par_iter()
+            .map(|&seed| {
+                let mut scenario = scenario.clone();
+                scenario.seed = Some(seed);
+                let mut simulation = Simulation::new(scenario, DEFAULT_WORLD_SIZE)?;
+                let mut met = 0;
+                while simulation.stopped.is_none() {
+                    simulation.step();
+                    met += self
+                        .objective
+                        .is_met(&simulation.slime_controller.population)
+                        as usize;
+                }
+                Ok(met as f64 / self.max_steps as f64)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(fitness.iter().sum::<f64>() / fitness.len().max(1) as f64)
+    }
+
+    /// Runs the search calling `on_generation` with the generation index and its
+    /// candidates sorted by fitness. Returns the best candidates of all generations
+    /// (as many as `population`), best first.
+    pub fn run(
+        &self,
+        mut on_generation: impl FnMut(usize, &[Candidate]),
+    ) -> Result<Vec<Candidate>, String> {
+        let base = self.base_scenario()?;
+        let state = rng::state();
+        rng::srand(self.seed);
+        let mut values = (0..self.population)
+            .map(|_| self.random_values())
+            .collect::<Vec<_>>();
+        let mut best = Vec::<Candidate>::new();
+        for generation in 0..self.generations {
+            // Keep the search random state away from the runs
+            let search_state = rng::state();
+            let mut candidates = values
+                .into_par_iter()
+                .map(|values| {
+                    let fitness = self.evaluate(&base, &values)?;
+                    Ok(Candidate { values, fitness })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            rng::set_state(search_state);
+            candidates.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+            on_generation(generation, &candidates);
+            values = candidates
+                .iter()
+                .take(self.elite)
+                .map(|c| c.values.clone())
+                .collect();
+            while values.len() < self.population {
+                let child = self.breed(self.select(&candidates), self.select(&candidates));
+                values.push(child);
+            }
+            // Stable sort keeps the earliest copy of the candidates kept as elite
+            best.extend(candidates);
+            best.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+            let mut kept = Vec::<Candidate>::with_capacity(self.population);
+            for candidate in best {
+                if (kept.len() < self.population)
+                    && kept.iter().all(|c| c.values != candidate.values)
+                {
+                    kept.push(candidate);
+                }
+            }
+            best = kept;
+        }
+        rng::set_state(state);
+        Ok(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn optimizer_works() {
+        let optimizer: Optimizer = toml::from_str(
+            r#"
+            seeds = [1, 2]
+            max_steps = 30.0
+            generations = 3
+            population = 6
+            elite = 2
+
+            [objective]
+            type = "population_range"
+            min = 1
+            max = 3
+
+            [[params]]
+            field = "initial_slimes"
+            range = [0.0, 6.0]
+            "#,
+        )
+        .unwrap();
+        let mut generations = 0;
+        let candidates = optimizer.run(|_, _| generations += 1).unwrap();
+        assert_eq!(generations, 3);
+        assert_eq!(candidates.len(), 6);
+        assert!(candidates.windows(2).all(|c| c[0].fitness >= c[1].fitness));
+        // Only a few slimes at the start can meet the objective
+        let best = &candidates[0];
+        assert!(best.fitness > 0.0);
+        assert!((0.5..3.5).contains(&best.values[0]));
+    }
+
+    #[test]
+    fn optimizer_keeps_best_of_all_generations() {
+        let optimizer: Optimizer = toml::from_str(
+            r#"
+            max_steps = 20.0
+            generations = 4
+            population = 4
+            mutation = 1.0
+
+            [objective]
+            type = "population_range"
+            min = 2
+            max = 4
+
+            [[params]]
+            field = "initial_slimes"
+            range = [0.0, 10.0]
+            "#,
+        )
+        .unwrap();
+        let mut max_fitness = f64::MIN;
+        let candidates = optimizer
+            .run(|_, candidates| max_fitness = max_fitness.max(candidates[0].fitness))
+            .unwrap();
+        assert!(candidates[0].fitness >= max_fitness);
+        assert!(candidates.windows(2).all(|c| c[0].values != c[1].values));
+    }
+}
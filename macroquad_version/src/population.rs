@@ -0,0 +1,121 @@
+//! # Population.
+//!
+//! Slimes stored as parallel arrays, one per field, so each part of the update step
+//! only reads the fields it needs and the neighbour searches scan contiguous positions
+//! and sizes. Slimes are removed with `swap_remove`, so their order is not kept.
+//!
+//! The rules using these fields (vision range, breeding, jumps...) live in `slime`.
+use crate::{
+    biome::BiomeModifiers,
+    disease::Health,
+    slime::{Behavior, SkillType, Skills, Slime, SlimeState, Traits},
+};
+use macroquad::prelude::*;
+
+/// Declares `Population` with one array for each field of `Slime`.
+macro_rules! population {
+    ($($vis:vis $field:ident: $t:ty,)*) => {
+        /// The slime at index `i` is made of the `i`th element of each array.
+        #[derive(Clone, Default)]
+        pub struct Population {
+            $($vis $field: Vec<$t>,)*
+        }
+
+        impl Population {
+            pub fn push(&mut self, slime: Slime) {
+                $(self.$field.push(slime.$field);)*
+            }
+
+            /// Removes the slime at `idx`, the last slime takes its place.
+            pub fn swap_remove(&mut self, idx: usize) -> Slime {
+                Slime {
+                    $($field: self.$field.swap_remove(idx),)*
+                }
+            }
+
+            /// Returns a copy of the slime at `idx`.
+            pub fn get(&self, idx: usize) -> Slime {
+                Slime {
+                    $($field: self.$field[idx].clone(),)*
+                }
+            }
+
+            pub fn clear(&mut self) {
+                $(self.$field.clear();)*
+            }
+        }
+    };
+}
+
+population! {
+    pub id: usize,
+    pub parents: Option<(usize, usize)>,
+    pub position: Vec2,
+    pub state: SlimeState,
+    pub skills: Skills,
+    pub biome: BiomeModifiers,
+    pub traits: Traits,
+    pub health: Health,
+    pub behavior: Behavior,
+    pub born: f32,
+    pub children: usize,
+    pub founder: usize,
+    pub generation: usize,
+    pub(crate) rest_until: f32,
+    pub(crate) speed: Vec2,
+    pub(crate) energy: f32,
+    pub(crate) size: f32,
+    pub(crate) last_jump: f32,
+    pub(crate) last_breed: f32,
+    pub(crate) next_skill_goal: f32,
+    pub(crate) skill_path: SkillType,
+}
+
+impl Population {
+    pub fn len(&self) -> usize {
+        self.id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.id.is_empty()
+    }
+
+    /// Iterates over copies of the slimes.
+    pub fn iter(&self) -> impl Iterator<Item = Slime> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    /// Removes every slime for which `f` returns true using `swap_remove`.
+    pub fn swap_remove_where(&mut self, mut f: impl FnMut(&Self, usize) -> bool) {
+        let mut i = 0;
+        while i < self.len() {
+            if f(self, i) {
+                self.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slime::SlimeConfig;
+
+    #[test]
+    fn swap_remove_where_works() {
+        let mut population = Population::default();
+        for i in 0..5 {
+            let mut slime = Slime::new(vec2(i as f32, 0.0), &SlimeConfig::default());
+            slime.id = i;
+            population.push(slime);
+        }
+        population.swap_remove_where(|p, i| p.id[i] % 2 == 0);
+        assert_eq!(population.len(), 2);
+        assert_eq!(population.id, vec![3, 1]);
+        // Every field moves with its slime
+        assert_eq!(population.position, vec![vec2(3.0, 0.0), vec2(1.0, 0.0)]);
+        assert_eq!(population.get(1).position, vec2(1.0, 0.0));
+    }
+}
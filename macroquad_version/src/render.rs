@@ -0,0 +1,324 @@
+//! # Frame rendering.
+//!
+//! Software rasterizer drawing the food, the slimes and the status of a simulation into
+//! an image, with the same sizes and colors as the window. It needs no window, so runs
+//! in batch can save frames to assemble time-lapses.
+use crate::{
+    coloring::{ColorMode, Palette, INFECTED_COLOR},
+    food::FOOD_SIZE,
+    simulation::Simulation,
+};
+use image::{Rgb, RgbImage};
+use macroquad::prelude::*;
+use serde::Deserialize;
+use std::{fs, path::PathBuf};
+
+const FOOD_COLOR: Color = GREEN;
+/// Pixels of each dot of the font.
+const TEXT_SCALE: u32 = 2;
+/// Columns and rows of dots of each character.
+const GLYPH_SIZE: (u32, u32) = (3, 5);
+
+/// Frames saved while a simulation runs.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FrameSettings {
+    /// Directory of the frames, each run writes to its own subdirectory.
+    pub dir: PathBuf,
+    /// Time steps between frames.
+    pub every: f32,
+    /// Pixels per world unit.
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    #[serde(default)]
+    pub color_mode: ColorMode,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+/// Image of the world, positions wrap around its edges.
+pub struct Frame {
+    pub image: RgbImage,
+    /// Pixels per world unit.
+    scale: f32,
+}
+
+impl Frame {
+    pub fn new(world_size: Vec2, scale: f32) -> Self {
+        let size = (world_size * scale).ceil().max(Vec2::ONE);
+        Self {
+            image: RgbImage::new(size.x as u32, size.y as u32),
+            scale,
+        }
+    }
+
+    fn put_pixel(&mut self, x: i64, y: i64, color: Color) {
+        let (width, height) = self.image.dimensions();
+        let x = x.rem_euclid(width as i64) as u32;
+        let y = y.rem_euclid(height as i64) as u32;
+        self.image.put_pixel(x, y, to_rgb(color));
+    }
+
+    /// Fills a rectangle given in world coordinates.
+    pub fn fill_rect(&mut self, rect: Rect, color: Color) {
+        let min = (rect.point() * self.scale).floor();
+        let max = ((rect.point() + rect.size()) * self.scale).ceil();
+        for y in min.y as i64..max.y as i64 {
+            for x in min.x as i64..max.x as i64 {
+                self.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    /// Fills the pixels with their center between `inner` and `outer` from `center`, in
+    /// world coordinates. An `inner` of 0 fills a circle.
+    pub fn fill_ring(&mut self, center: Vec2, inner: f32, outer: f32, color: Color) {
+        let min = ((center - Vec2::splat(outer)) * self.scale).floor();
+        let max = ((center + Vec2::splat(outer)) * self.scale).ceil();
+        for y in min.y as i64..max.y as i64 {
+            for x in min.x as i64..max.x as i64 {
+                let pixel = vec2(x as f32 + 0.5, y as f32 + 0.5) / self.scale;
+                let distance = pixel.distance(center);
+                if distance >= inner && distance <= outer {
+                    self.put_pixel(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// Draws `text` with a small bitmap font, `position` is its top left corner in pixels.
+    pub fn draw_text(&mut self, text: &str, position: (i64, i64), color: Color) {
+        let advance = ((GLYPH_SIZE.0 + 1) * TEXT_SCALE) as i64;
+        for (i, c) in text.chars().enumerate() {
+            let left = position.0 + i as i64 * advance;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..GLYPH_SIZE.0 {
+                    if bits >> (GLYPH_SIZE.0 - 1 - col) & 1 == 0 {
+                        continue;
+                    }
+                    for dy in 0..TEXT_SCALE {
+                        for dx in 0..TEXT_SCALE {
+                            self.put_pixel(
+                                left + (col * TEXT_SCALE + dx) as i64,
+                                position.1 + (row as u32 * TEXT_SCALE + dy) as i64,
+                                color,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Width in pixels of `text` drawn with `Frame::draw_text`.
+fn text_width(text: &str) -> u32 {
+    (text.chars().count() as u32 * (GLYPH_SIZE.0 + 1)).saturating_sub(1) * TEXT_SCALE
+}
+
+fn to_rgb(color: Color) -> Rgb<u8> {
+    Rgb([color.r, color.g, color.b].map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8))
+}
+
+/// Rows of dots of a character, lowercase letters are drawn uppercase and unknown
+/// characters blank.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        _ => [0; 5],
+    }
+}
+
+/// Draws the food, the slimes colored by `mode` and the status on the top right corner.
+pub fn render(simulation: &Simulation, mode: ColorMode, scale: f32) -> Frame {
+    let mut frame = Frame::new(simulation.world_size, scale);
+    for food in simulation.food_controller.population.iter() {
+        let rect = Rect::new(food.position.x, food.position.y, FOOD_SIZE, FOOD_SIZE);
+        frame.fill_rect(rect, FOOD_COLOR);
+    }
+    let controller = &simulation.slime_controller;
+    let population = &controller.population;
+    let palette = Palette::new(mode, controller, simulation.time);
+    for i in 0..population.len() {
+        let (position, size) = (population.position[i], population.size[i]);
+        frame.fill_ring(position, 0.0, size, palette.color(i));
+        if population.health[i].is_infected() {
+            frame.fill_ring(position, size + 0.5, size + 1.5, INFECTED_COLOR);
+        }
+    }
+    let stats = simulation.stats();
+    let mut entries = vec![
+        (format!("Time: {:.0}", simulation.time), LIGHTGRAY),
+        (format!("Slimes: {}", stats.slimes), LIGHTGRAY),
+        (format!("Food: {}", stats.food), LIGHTGRAY),
+        (format!("Vision: {}", stats.vision), ORANGE),
+        (format!("Efficiency: {}", stats.efficiency), PURPLE),
+        (format!("Jumper: {}", stats.jumper), PINK),
+    ];
+    if controller.disease.enabled {
+        entries.push((format!("Infected: {}", stats.infected), INFECTED_COLOR));
+    }
+    let width = frame.image.width() as i64;
+    let line_height = ((GLYPH_SIZE.1 + 2) * TEXT_SCALE) as i64;
+    for (i, (text, color)) in entries.iter().enumerate() {
+        let x = width - text_width(text) as i64 - 5;
+        frame.draw_text(text, (x, 5 + i as i64 * line_height), *color);
+    }
+    frame
+}
+
+/// Saves a frame of a simulation every `FrameSettings::every` time steps.
+pub struct FrameWriter {
+    settings: FrameSettings,
+    /// Directory of the frames of this run.
+    dir: PathBuf,
+    /// Time of the next frame.
+    next: f32,
+    /// Time of the last saved frame.
+    last: Option<f32>,
+}
+
+impl FrameWriter {
+    pub fn new(settings: &FrameSettings, dir: PathBuf) -> Result<Self, String> {
+        if settings.every <= 0.0 {
+            return Err("Frames need a positive interval".to_string());
+        }
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        Ok(Self {
+            settings: settings.clone(),
+            dir,
+            next: 0.0,
+            last: None,
+        })
+    }
+
+    /// Saves a frame when one is due.
+    pub fn update(&mut self, simulation: &Simulation) -> Result<(), String> {
+        if simulation.time < self.next {
+            return Ok(());
+        }
+        self.save(simulation)
+    }
+
+    /// Saves the final state of a run, unless its frame was already saved.
+    pub fn finish(&mut self, simulation: &Simulation) -> Result<(), String> {
+        if self.last == Some(simulation.time) {
+            return Ok(());
+        }
+        self.save(simulation)
+    }
+
+    /// Saves a frame named after the simulation time.
+    fn save(&mut self, simulation: &Simulation) -> Result<(), String> {
+        let frame = render(simulation, self.settings.color_mode, self.settings.scale);
+        let path = self
+            .dir
+            .join(format!("frame_{:08}.png", simulation.time as u64));
+        frame.image.save(path).map_err(|e| e.to_string())?;
+        self.last = Some(simulation.time);
+        self.next = ((simulation.time / self.settings.every).floor() + 1.0) * self.settings.every;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scenario::Scenario, simulation::DEFAULT_WORLD_SIZE};
+
+    #[test]
+    fn frame_writer_works() {
+        let dir = std::env::temp_dir().join("evolution_simulation_frames");
+        let _ = fs::remove_dir_all(&dir);
+        let settings = FrameSettings {
+            dir: dir.clone(),
+            every: 10.0,
+            scale: 0.1,
+            color_mode: ColorMode::State,
+        };
+        let mut writer = FrameWriter::new(&settings, dir.clone()).unwrap();
+        let mut simulation = Simulation::new(Scenario::default(), DEFAULT_WORLD_SIZE).unwrap();
+        for _ in 0..25 {
+            writer.update(&simulation).unwrap();
+            simulation.step();
+        }
+        writer.finish(&simulation).unwrap();
+        writer.finish(&simulation).unwrap();
+        let mut names = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            [0, 10, 20, 25].map(|time| format!("frame_{:08}.png", time))
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn render_works() {
+        let scenario = Scenario {
+            seed: Some(3),
+            ..Scenario::default()
+        };
+        let simulation = Simulation::new(scenario, DEFAULT_WORLD_SIZE).unwrap();
+        let frame = render(&simulation, ColorMode::Lineage, 0.5);
+        assert_eq!(frame.image.dimensions(), (400, 300));
+        let palette = Palette::new(ColorMode::Lineage, &simulation.slime_controller, 0.0);
+        let population = &simulation.slime_controller.population;
+        let i = population.len() - 1;
+        let center = population.position[i] * 0.5;
+        assert_eq!(
+            *frame.image.get_pixel(center.x as u32, center.y as u32),
+            to_rgb(palette.color(i))
+        );
+        // The status text is drawn on the top right corner
+        assert!((380..400).any(|x| *frame.image.get_pixel(x, 6) == to_rgb(LIGHTGRAY)));
+
+        let mut frame = Frame::new(vec2(10.0, 10.0), 1.0);
+        frame.fill_rect(Rect::new(8.0, 8.0, 4.0, 4.0), GREEN);
+        assert_eq!(*frame.image.get_pixel(1, 1), to_rgb(GREEN), "Wraps");
+        assert_eq!(*frame.image.get_pixel(7, 7), to_rgb(BLACK));
+    }
+}
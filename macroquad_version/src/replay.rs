@@ -0,0 +1,297 @@
+//! # Replays.
+//!
+//! A run recorded as the scenario it started from (with its seed) and the interventions
+//! made from the UI with their times. Every random number comes from the simulation
+//! generator, so playing the actions back at the same times gives the same run, at
+//! whatever speed it is played.
+use crate::{event::Event, scenario::Scenario, simulation::Simulation};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// An intervention on a running simulation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// Sets scenario fields (dotted paths, as in `Scenario::with_fields`).
+    Settings {
+        fields: BTreeMap<String, toml::Value>,
+    },
+    Reset,
+    SpawnFood,
+    SpawnSlime,
+    InfectSlime,
+    GenerateBiomes,
+    Event {
+        event: Event,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedAction {
+    /// Simulation time when the action happened, it runs before the step at this time.
+    pub time: f32,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub scenario: Scenario,
+    #[serde(default)]
+    pub actions: Vec<RecordedAction>,
+}
+
+impl Replay {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        toml::from_str(&content).map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let content = toml::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(path, content).map_err(|e| e.to_string())
+    }
+}
+
+/// Records the actions applied to a simulation. Settings changed directly on the
+/// simulation (e.g. by sliders) are found comparing its scenario with the last one.
+pub struct Recorder {
+    pub replay: Replay,
+    settings: toml::Value,
+}
+
+impl Recorder {
+    /// Starts recording `simulation`, which should have just been built from its scenario
+    /// with a seed.
+    pub fn new(simulation: &Simulation) -> Result<Self, String> {
+        let scenario = simulation.scenario();
+        if scenario.seed.is_none() {
+            return Err("Only seeded simulations can be recorded".to_string());
+        }
+        Ok(Self {
+            settings: toml::Value::try_from(&scenario).map_err(|e| e.to_string())?,
+            replay: Replay {
+                scenario,
+                actions: Vec::new(),
+            },
+        })
+    }
+
+    /// Records the settings that changed since the last check.
+    pub fn check_settings(&mut self, simulation: &Simulation) {
+        let settings = match toml::Value::try_from(simulation.scenario()) {
+            Ok(settings) => settings,
+            Err(_) => return,
+        };
+        let mut fields = BTreeMap::new();
+        changed_fields("", &self.settings, &settings, &mut fields);
+        if !fields.is_empty() {
+            self.replay.actions.push(RecordedAction {
+                time: simulation.time,
+                action: Action::Settings { fields },
+            });
+        }
+        self.settings = settings;
+    }
+
+    /// Applies `action` to the simulation and records it.
+    pub fn apply(&mut self, simulation: &mut Simulation, action: Action) -> Result<(), String> {
+        self.check_settings(simulation);
+        let time = simulation.time;
+        simulation.apply_action(&action)?;
+        self.replay.actions.push(RecordedAction { time, action });
+        // Settings changed by the action itself are not recorded again
+        self.settings = toml::Value::try_from(simulation.scenario()).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+/// Collects the fields of `new` that differ from `old`, arrays are compared as a whole.
+/// Fields removed from `new` are not tracked.
+fn changed_fields(
+    prefix: &str,
+    old: &toml::Value,
+    new: &toml::Value,
+    fields: &mut BTreeMap<String, toml::Value>,
+) {
+    match (old, new) {
+        (toml::Value::Table(old), toml::Value::Table(new)) => {
+            for (key, value) in new {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                match old.get(key) {
+                    Some(old) => changed_fields(&path, old, value, fields),
+                    None => {
+                        fields.insert(path, value.clone());
+                    }
+                }
+            }
+        }
+        (old, new) if old != new => {
+            fields.insert(prefix.to_string(), new.clone());
+        }
+        _ => {}
+    }
+}
+
+/// Plays a replay back on a simulation built from its scenario. Settings changed
+/// outside the replay make the run diverge, they are found comparing the scenario with
+/// the one from the last `sync`.
+pub struct Player {
+    pub replay: Replay,
+    /// Index of the next action to apply.
+    next: usize,
+    settings: Option<toml::Value>,
+}
+
+impl Player {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            next: 0,
+            settings: None,
+        }
+    }
+
+    /// Applies the actions due before the next step of `simulation`.
+    pub fn apply(&mut self, simulation: &mut Simulation) -> Result<(), String> {
+        while let Some(recorded) = self.replay.actions.get(self.next) {
+            if recorded.time > simulation.time {
+                break;
+            }
+            self.next += 1;
+            simulation.apply_action(&recorded.action)?;
+        }
+        Ok(())
+    }
+
+    /// True when every action was applied.
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.replay.actions.len()
+    }
+
+    /// Remembers the settings of `simulation` after the replay ran, including the
+    /// changes made by its actions and events.
+    pub fn sync(&mut self, simulation: &Simulation) {
+        self.settings = toml::Value::try_from(simulation.scenario()).ok();
+    }
+
+    /// True if the settings of `simulation` changed since the last `sync`.
+    pub fn settings_changed(&self, simulation: &Simulation) -> bool {
+        match (&self.settings, toml::Value::try_from(simulation.scenario())) {
+            (Some(old), Ok(new)) => *old != new,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{obstacle::Obstacle, simulation::DEFAULT_WORLD_SIZE};
+    use macroquad::prelude::*;
+
+    #[test]
+    fn replay_works() {
+        let scenario = Scenario {
+            seed: Some(7),
+            ..Scenario::default()
+        };
+        let mut simulation = Simulation::new(scenario, DEFAULT_WORLD_SIZE).unwrap();
+        let mut recorder = Recorder::new(&simulation).unwrap();
+        for i in 0..600 {
+            match i {
+                100 => simulation.slime_controller.config.vision_range = 120.0,
+                150 => recorder.apply(&mut simulation, Action::SpawnSlime).unwrap(),
+                200 => simulation.obstacles.push(Obstacle::Circle {
+                    center: vec2(100.0, 100.0),
+                    radius: 30.0,
+                }),
+                300 => recorder
+                    .apply(
+                        &mut simulation,
+                        Action::Event {
+                            event: Event::Bloom { amount: 20 },
+                        },
+                    )
+                    .unwrap(),
+                400 => recorder.apply(&mut simulation, Action::Reset).unwrap(),
+                450 => recorder.apply(&mut simulation, Action::SpawnFood).unwrap(),
+                _ => {}
+            }
+            recorder.check_settings(&simulation);
+            simulation.step();
+        }
+        // The replay file can be read back
+        let content = toml::to_string(&recorder.replay).unwrap();
+        let replay: Replay = toml::from_str(&content).unwrap();
+        assert_eq!(replay.actions, recorder.replay.actions);
+        assert_eq!(replay.actions.len(), 6);
+
+        let mut player = Player::new(replay);
+        let mut played =
+            Simulation::new(player.replay.scenario.clone(), DEFAULT_WORLD_SIZE).unwrap();
+        for _ in 0..600 {
+            player.apply(&mut played).unwrap();
+            played.step();
+            player.sync(&played);
+            assert!(!player.settings_changed(&played));
+        }
+        assert!(player.is_finished());
+        assert_eq!(played.time, simulation.time);
+        assert_eq!(played.obstacles.obstacles.len(), 1);
+        assert_eq!(
+            played.slime_controller.population.position,
+            simulation.slime_controller.population.position
+        );
+        assert_eq!(
+            played.food_controller.population.len(),
+            simulation.food_controller.population.len()
+        );
+    }
+
+    #[test]
+    fn settings_changed_works() {
+        let scenario = Scenario {
+            seed: Some(7),
+            ..Scenario::default()
+        };
+        let mut simulation = Simulation::new(scenario.clone(), DEFAULT_WORLD_SIZE).unwrap();
+        let mut player = Player::new(Replay {
+            scenario,
+            actions: vec![RecordedAction {
+                time: 10.0,
+                action: Action::Event {
+                    event: Event::Plague { infected: 1 },
+                },
+            }],
+        });
+        for _ in 0..20 {
+            player.apply(&mut simulation).unwrap();
+            simulation.step();
+            player.sync(&simulation);
+        }
+        // Changes made by the replay itself don't count
+        assert!(simulation.slime_controller.disease.enabled);
+        assert!(!player.settings_changed(&simulation));
+        simulation.slime_controller.config.vision_range = 120.0;
+        assert!(player.settings_changed(&simulation));
+    }
+
+    #[test]
+    fn changed_fields_works() {
+        let old = toml::Value::try_from(Scenario::default()).unwrap();
+        let mut scenario = Scenario::default();
+        scenario.slime.vision_skill = 3.0;
+        scenario.initial_slimes = 9;
+        let new = toml::Value::try_from(&scenario).unwrap();
+        let mut fields = BTreeMap::new();
+        changed_fields("", &old, &new, &mut fields);
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields["slime.vision_skill"], toml::Value::Float(3.0));
+        assert_eq!(fields["initial_slimes"], toml::Value::Integer(9));
+    }
+}
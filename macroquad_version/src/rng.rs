@@ -0,0 +1,101 @@
+//! # Random numbers.
+//!
+//! PCG generator with one state per thread, so simulations running in parallel are
+//! independent and a simulation seeded with `srand` is reproducible.
+use std::cell::Cell;
+
+const INCREMENT: u64 = 1442695040888963407;
+const MULTIPLIER: u64 = 6364136223846793005;
+
+thread_local! {
+    static STATE: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Seeds the generator of the current thread.
+pub fn srand(seed: u64) {
+    set_state(0);
+    rand();
+    set_state(state().wrapping_add(seed));
+    rand();
+}
+
+/// Current state of the generator, it can be restored with `set_state`.
+pub fn state() -> u64 {
+    STATE.with(|s| s.get())
+}
+
+pub fn set_state(state: u64) {
+    STATE.with(|s| s.set(state));
+}
+
+/// Returns a random number in the range [0, u32::MAX].
+pub fn rand() -> u32 {
+    let old = state();
+    set_state(old.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT));
+    let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+    xorshifted.rotate_right((old >> 59) as u32)
+}
+
+/// Returns a random number in the range [0, 1).
+fn unit() -> f64 {
+    rand() as f64 / (u32::MAX as f64 + 1.0)
+}
+
+pub trait RandomRange {
+    /// Random value in the range [low, high).
+    fn gen_range(low: Self, high: Self) -> Self;
+}
+
+impl RandomRange for f32 {
+    fn gen_range(low: Self, high: Self) -> Self {
+        low + (high - low) * unit() as f32
+    }
+}
+
+impl RandomRange for f64 {
+    fn gen_range(low: Self, high: Self) -> Self {
+        low + (high - low) * unit()
+    }
+}
+
+macro_rules! impl_random_range_int {
+    ($($t:ty),*) => {
+        $(
+            impl RandomRange for $t {
+                fn gen_range(low: Self, high: Self) -> Self {
+                    if high <= low {
+                        return low;
+                    }
+                    let span = (high as i128 - low as i128) as f64;
+                    (low as i128 + (unit() * span) as i128) as Self
+                }
+            }
+        )*
+    };
+}
+
+impl_random_range_int!(i32, i64, u32, u64, usize);
+
+pub fn gen_range<T: RandomRange>(low: T, high: T) -> T {
+    T::gen_range(low, high)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_is_reproducible() {
+        srand(7);
+        let a = (0..10).map(|_| gen_range(0.0, 1.0)).collect::<Vec<f32>>();
+        srand(7);
+        let b = (0..10).map(|_| gen_range(0.0, 1.0)).collect::<Vec<f32>>();
+        assert_eq!(a, b);
+        assert!((0..1000).all(|_| gen_range(0, 3) < 3));
+        assert!((0..1000).all(|_| (-1.0..1.0).contains(&gen_range(-1.0, 1.0))));
+        // Each thread has its own state
+        let state = state();
+        std::thread::spawn(|| gen_range(0, 10)).join().unwrap();
+        assert_eq!(state, super::state());
+    }
+}
@@ -0,0 +1,516 @@
+//! # Scenarios.
+//!
+//! A whole experiment in one TOML file: world size, seed, initial entities, settings
+//! of every controller, obstacles, biomes, scheduled events and when to stop.
+use crate::{
+    biome::{Biome, BiomeMap, BiomeModifiers},
+    disease::DiseaseConfig,
+    event::{EventSchedule, ScheduledEvent},
+    food::FoodController,
+    obstacle::{Obstacle, ObstacleMap},
+    scent::ScentField,
+    slime::{BehaviorRules, GroupRules, SlimeConfig, SlimeController},
+    utils::serde_vec2,
+};
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// Directory listed by the scenario picker.
+pub const SCENARIOS_DIR: &str = "scenarios";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    /// Seed of the random generator, the simulation is not reproducible without it.
+    pub seed: Option<u64>,
+    /// Size of the world, `DEFAULT_WORLD_SIZE` is used when missing.
+    #[serde(with = "serde_vec2::option")]
+    pub world_size: Option<Vec2>,
+    /// Food spawned at random positions on reset (besides the placed ones).
+    pub initial_food: usize,
+    /// Slimes spawned at random positions on reset (besides the placed ones).
+    pub initial_slimes: usize,
+    pub slime: SlimeConfig,
+    pub slimes: SlimeSettings,
+    pub food: FoodSettings,
+    pub scent: ScentSettings,
+    pub biomes: BiomeSettings,
+    pub placed: PlacedEntities,
+    pub stop: StopConditions,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub obstacles: Vec<Obstacle>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<ScheduledEvent>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            world_size: None,
+            initial_food: 20,
+            initial_slimes: 4,
+            slime: SlimeConfig::default(),
+            slimes: SlimeSettings::default(),
+            food: FoodSettings::default(),
+            scent: ScentSettings::default(),
+            biomes: BiomeSettings::default(),
+            placed: PlacedEntities::default(),
+            stop: StopConditions::default(),
+            obstacles: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+}
+
+/// Parameters of the `SlimeController`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlimeSettings {
+    pub time_cost_freq: f32,
+    pub breeding_cooldown: f32,
+    pub max_size_slow: f32,
+    pub collisions: bool,
+    pub crowding_cost: f32,
+    pub crowding_radius: f32,
+    pub behavior: BehaviorRules,
+    pub group: GroupRules,
+    pub disease: DiseaseConfig,
+}
+
+impl Default for SlimeSettings {
+    fn default() -> Self {
+        Self {
+            time_cost_freq: 8.0,
+            breeding_cooldown: 150.0,
+            max_size_slow: 0.5,
+            collisions: false,
+            crowding_cost: 0.0,
+            crowding_radius: 10.0,
+            behavior: BehaviorRules::default(),
+            group: GroupRules::default(),
+            disease: DiseaseConfig::default(),
+        }
+    }
+}
+
+impl From<&SlimeController> for SlimeSettings {
+    fn from(controller: &SlimeController) -> Self {
+        Self {
+            time_cost_freq: controller.time_cost_freq,
+            breeding_cooldown: controller.breeding_cooldown,
+            max_size_slow: controller.max_size_slow,
+            collisions: controller.collisions,
+            crowding_cost: controller.crowding_cost,
+            crowding_radius: controller.crowding_radius,
+            behavior: controller.behavior_rules.clone(),
+            group: controller.group_rules.clone(),
+            disease: controller.disease.clone(),
+        }
+    }
+}
+
+impl SlimeSettings {
+    /// Sets the parameters of a running controller.
+    pub fn apply(&self, controller: &mut SlimeController) {
+        controller.time_cost_freq = self.time_cost_freq;
+        controller.breeding_cooldown = self.breeding_cooldown;
+        controller.max_size_slow = self.max_size_slow;
+        controller.collisions = self.collisions;
+        controller.crowding_cost = self.crowding_cost;
+        controller.crowding_radius = self.crowding_radius;
+        controller.behavior_rules = self.behavior.clone();
+        controller.group_rules = self.group.clone();
+        controller.disease = self.disease.clone();
+    }
+}
+
+/// Parameters of the `FoodController`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FoodSettings {
+    pub spawn_time: f32,
+    pub limit: f32,
+    pub energy_range: (f32, f32),
+    pub speed_range: (f32, f32),
+}
+
+impl Default for FoodSettings {
+    fn default() -> Self {
+        Self {
+            spawn_time: 15.0,
+            limit: 300.0,
+            energy_range: (30.0, 90.0),
+            speed_range: (0.85, 2.2),
+        }
+    }
+}
+
+impl From<&FoodController> for FoodSettings {
+    fn from(controller: &FoodController) -> Self {
+        Self {
+            spawn_time: controller.spawn_time,
+            limit: controller.limit,
+            energy_range: controller.energy_range,
+            speed_range: controller.speed_range,
+        }
+    }
+}
+
+impl FoodSettings {
+    /// Sets the parameters of a running controller.
+    pub fn apply(&self, controller: &mut FoodController) {
+        controller.spawn_time = self.spawn_time;
+        controller.limit = self.limit;
+        controller.energy_range = self.energy_range;
+        controller.speed_range = self.speed_range;
+    }
+}
+
+/// Parameters of the `ScentField`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScentSettings {
+    pub enabled: bool,
+    pub diffusion: f32,
+    pub evaporation: f32,
+    pub eat_deposit: f32,
+    pub food_emission: f32,
+}
+
+impl Default for ScentSettings {
+    fn default() -> Self {
+        Self::from(&ScentField::new(Vec2::ONE))
+    }
+}
+
+impl From<&ScentField> for ScentSettings {
+    fn from(scent: &ScentField) -> Self {
+        Self {
+            enabled: scent.enabled,
+            diffusion: scent.diffusion,
+            evaporation: scent.evaporation,
+            eat_deposit: scent.eat_deposit,
+            food_emission: scent.food_emission,
+        }
+    }
+}
+
+impl ScentSettings {
+    /// Sets the parameters of a running field.
+    pub fn apply(&self, scent: &mut ScentField) {
+        scent.enabled = self.enabled;
+        scent.diffusion = self.diffusion;
+        scent.evaporation = self.evaporation;
+        scent.eat_deposit = self.eat_deposit;
+        scent.food_emission = self.food_emission;
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BiomeSettings {
+    pub enabled: bool,
+    /// Image with the map (see `BiomeMap::load`), relative to the scenario file.
+    /// A random map is generated when missing.
+    pub image: Option<PathBuf>,
+    /// Modifiers for each biome, in the order swamp, plain and desert.
+    pub modifiers: [BiomeModifiers; 3],
+}
+
+impl Default for BiomeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            image: None,
+            modifiers: Biome::ALL.map(BiomeModifiers::for_biome),
+        }
+    }
+}
+
+/// Entities spawned at fixed positions on reset.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlacedEntities {
+    #[serde(with = "serde_vec2::vec")]
+    pub food: Vec<Vec2>,
+    pub slimes: Vec<PlacedSlime>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlacedSlime {
+    #[serde(with = "serde_vec2")]
+    pub position: Vec2,
+    /// Starting (vision, efficiency, jumper) skill levels.
+    #[serde(default)]
+    pub skills: (usize, usize, usize),
+}
+
+/// When the simulation stops, every condition is optional.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StopConditions {
+    pub max_steps: Option<f32>,
+    /// Stop when there are no slimes left.
+    pub extinction: bool,
+    /// Stop when the population drops below this.
+    pub min_population: Option<usize>,
+    /// Stop when the population goes above this.
+    pub max_population: Option<usize>,
+}
+
+impl StopConditions {
+    /// Returns the reason to stop the simulation, if any.
+    pub fn check(&self, time: f32, population: usize) -> Option<String> {
+        if self.max_steps.is_some_and(|max| time >= max) {
+            return Some("Max steps".to_string());
+        }
+        if self.extinction && (population == 0) {
+            return Some("Extinction".to_string());
+        }
+        if let Some(min) = self.min_population.filter(|&min| population < min) {
+            return Some(format!("Population below {}", min));
+        }
+        self.max_population
+            .filter(|&max| population > max)
+            .map(|max| format!("Population above {}", max))
+    }
+}
+
+impl Scenario {
+    /// Loads a scenario from a TOML file, see `scenarios/example.toml`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut scenario: Self = toml::from_str(&content).map_err(|e| e.to_string())?;
+        if let (Some(image), Some(dir)) = (&scenario.biomes.image, path.parent()) {
+            scenario.biomes.image = Some(dir.join(image));
+        }
+        Ok(scenario)
+    }
+
+    /// Returns a copy of the scenario with the given fields replaced, each field is a
+    /// path separated by dots (e.g. `slime.vision_skill` or `stop.max_population`).
+    pub fn with_fields(&self, fields: &[(String, toml::Value)]) -> Result<Self, String> {
+        let mut value = toml::Value::try_from(self).map_err(|e| e.to_string())?;
+        for (field, new_value) in fields {
+            let (parents, key) = match field.rsplit_once('.') {
+                Some((parents, key)) => (Some(parents), key),
+                None => (None, field.as_str()),
+            };
+            let not_table = || format!("Field {} is not inside a table", field);
+            let mut table = value.as_table_mut().ok_or_else(not_table)?;
+            for parent in parents.into_iter().flat_map(|p| p.split('.')) {
+                table = table
+                    .entry(parent)
+                    .or_insert_with(|| toml::Value::Table(Default::default()))
+                    .as_table_mut()
+                    .ok_or_else(not_table)?;
+            }
+            let new_value = match (table.get(key), new_value) {
+                // Keep integer fields as integers
+                (Some(toml::Value::Integer(_)), toml::Value::Float(f)) => {
+                    toml::Value::Integer(f.round() as i64)
+                }
+                _ => new_value.clone(),
+            };
+            table.insert(key.to_string(), new_value);
+        }
+        let scenario: Self = value.try_into().map_err(|e| e.to_string())?;
+        // Unknown fields are dropped when deserializing, so they are missing here
+        let known = toml::Value::try_from(&scenario).map_err(|e| e.to_string())?;
+        for (field, _) in fields {
+            let mut current = Some(&known);
+            for key in field.split('.') {
+                current = current.and_then(|value| value.get(key));
+            }
+            if current.is_none() {
+                return Err(format!("Unknown field {}", field));
+            }
+        }
+        Ok(scenario)
+    }
+
+    /// Saves the scenario as TOML, the biome image path is rewritten relative to the
+    /// saved file so it can be loaded back.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let mut scenario = self.clone();
+        if let Some(image) = &mut scenario.biomes.image {
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            *image = relative_path(image, dir)?;
+        }
+        let content = toml::to_string(&scenario).map_err(|e| e.to_string())?;
+        fs::write(path, content).map_err(|e| e.to_string())
+    }
+
+    /// Lists the scenario files in `dir`.
+    pub fn list(dir: impl AsRef<Path>) -> Vec<PathBuf> {
+        let mut files = fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| p.extension().is_some_and(|ext| ext == "toml"))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        files.sort();
+        files
+    }
+
+    pub fn food_controller(&self, world_size: Vec2) -> FoodController {
+        let food = &self.food;
+        FoodController::new(
+            world_size,
+            food.spawn_time,
+            food.limit,
+            food.energy_range,
+            food.speed_range,
+        )
+    }
+
+    pub fn slime_controller(&self, world_size: Vec2) -> SlimeController {
+        let settings = &self.slimes;
+        let mut controller = SlimeController::new(
+            world_size,
+            self.slime.clone(),
+            settings.time_cost_freq,
+            settings.breeding_cooldown,
+            settings.max_size_slow,
+        );
+        controller.collisions = settings.collisions;
+        controller.crowding_cost = settings.crowding_cost;
+        controller.crowding_radius = settings.crowding_radius;
+        controller.behavior_rules = settings.behavior.clone();
+        controller.group_rules = settings.group.clone();
+        controller.disease = settings.disease.clone();
+        controller
+    }
+
+    pub fn obstacle_map(&self) -> ObstacleMap {
+        ObstacleMap {
+            obstacles: self.obstacles.clone(),
+        }
+    }
+
+    pub fn event_schedule(&self) -> EventSchedule {
+        EventSchedule {
+            events: self.events.clone(),
+        }
+    }
+
+    pub fn biome_map(&self, world_size: Vec2) -> Result<BiomeMap, String> {
+        let settings = &self.biomes;
+        let mut biomes = match (&settings.image, settings.enabled) {
+            (_, false) => BiomeMap::uniform(world_size, Biome::Plain),
+            (Some(image), true) => BiomeMap::load(image, world_size)?,
+            (None, true) => BiomeMap::generate(world_size),
+        };
+        biomes.modifiers = settings.modifiers;
+        Ok(biomes)
+    }
+
+    pub fn scent_field(&self, world_size: Vec2) -> ScentField {
+        let settings = &self.scent;
+        let mut scent = ScentField::new(world_size);
+        scent.enabled = settings.enabled;
+        scent.diffusion = settings.diffusion;
+        scent.evaporation = settings.evaporation;
+        scent.eat_deposit = settings.eat_deposit;
+        scent.food_emission = settings.food_emission;
+        scent
+    }
+}
+
+/// Returns `path` relative to the directory `base`, or absolute when it can't be.
+fn relative_path(path: &Path, base: &Path) -> Result<PathBuf, String> {
+    let path = std::path::absolute(path).map_err(|e| e.to_string())?;
+    let base = std::path::absolute(base).map_err(|e| e.to_string())?;
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let ups = base.components().skip(common);
+    if (common == 0) || ups.clone().any(|c| !matches!(c, Component::Normal(_))) {
+        return Ok(path);
+    }
+    Ok(ups
+        .map(|_| Component::ParentDir)
+        .chain(path.components().skip(common))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_works() {
+        let scenario: Scenario = toml::from_str(include_str!("../scenarios/example.toml")).unwrap();
+        assert_eq!(scenario.seed, Some(42));
+        assert_eq!(scenario.placed.slimes.len(), 2);
+        assert_eq!(scenario.stop.check(10.0, 0), Some("Extinction".to_string()));
+        assert_eq!(scenario.stop.check(10.0, 100), None);
+        // Saved scenarios can be loaded back
+        let content = toml::to_string(&scenario).unwrap();
+        let loaded: Scenario = toml::from_str(&content).unwrap();
+        assert_eq!(loaded.obstacles, scenario.obstacles);
+        assert_eq!(loaded.events.len(), scenario.events.len());
+        assert_eq!(loaded.slime.vision_range, scenario.slime.vision_range);
+    }
+
+    #[test]
+    fn with_fields_works() {
+        let scenario = Scenario::default()
+            .with_fields(&[
+                ("slime.vision_skill".to_string(), toml::Value::Float(3.0)),
+                ("initial_slimes".to_string(), toml::Value::Float(9.6)),
+                ("stop.max_population".to_string(), toml::Value::Integer(50)),
+            ])
+            .unwrap();
+        assert_eq!(scenario.slime.vision_skill, 3.0);
+        assert_eq!(scenario.initial_slimes, 10);
+        assert_eq!(scenario.stop.max_population, Some(50));
+        let invalid = Scenario::default().with_fields(&[(
+            "slime.vision_skill.level".to_string(),
+            toml::Value::Float(3.0),
+        )]);
+        assert!(invalid.is_err());
+        for misspelled in ["slime.vison_skill", "slimes.breeding_cooldwn"] {
+            let invalid = Scenario::default()
+                .with_fields(&[(misspelled.to_string(), toml::Value::Float(3.0))]);
+            assert!(invalid.is_err());
+        }
+    }
+
+    #[test]
+    fn save_load_keeps_image_path() {
+        let dir = std::env::temp_dir().join("scenario_image_path");
+        let nested = dir.join("nested");
+        fs::create_dir_all(&nested).unwrap();
+        let original = dir.join("original.toml");
+        fs::write(&original, "[biomes]\nimage = \"map.png\"\n").unwrap();
+        fs::write(dir.join("map.png"), "").unwrap();
+        let scenario = Scenario::load(&original).unwrap();
+        assert_eq!(scenario.biomes.image, Some(dir.join("map.png")));
+        // Saved next to the original and in another directory
+        for saved in [dir.join("saved.toml"), nested.join("saved.toml")] {
+            scenario.save(&saved).unwrap();
+            let loaded = Scenario::load(&saved).unwrap();
+            let image = loaded.biomes.image.unwrap();
+            assert_eq!(
+                fs::canonicalize(image).unwrap(),
+                fs::canonicalize(dir.join("map.png")).unwrap()
+            );
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
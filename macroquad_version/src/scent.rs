@@ -0,0 +1,168 @@
+//! # Scent field.
+//!
+//! Grid of scent values that diffuse to its neighbours and evaporate over time.
+//! Food emits scent and slimes leave scent when they eat, slimes without a target
+//! in vision follow its gradient.
+use crate::food::Food;
+use macroquad::prelude::*;
+
+/// Side of the scent cells.
+pub const SCENT_CELL_SIZE: f32 = 10.0;
+/// Scent value drawn with full opacity.
+const SCENT_DRAW_MAX: f32 = 20.0;
+
+#[derive(Clone)]
+pub struct ScentField {
+    pub enabled: bool,
+    /// Draw the field as an overlay.
+    pub visible: bool,
+    /// Fraction of each cell's scent mixed with its neighbours every step.
+    pub diffusion: f32,
+    /// Fraction of scent lost every step.
+    pub evaporation: f32,
+    /// Scent deposited by slimes when eating.
+    pub eat_deposit: f32,
+    /// Scent emitted by each food every step.
+    pub food_emission: f32,
+    values: Vec<f32>,
+    buffer: Vec<f32>,
+    cols: usize,
+    rows: usize,
+}
+
+impl ScentField {
+    pub fn new(world_size: Vec2) -> Self {
+        let cols = (world_size.x / SCENT_CELL_SIZE).ceil().max(1.0) as usize;
+        let rows = (world_size.y / SCENT_CELL_SIZE).ceil().max(1.0) as usize;
+        Self {
+            enabled: false,
+            visible: false,
+            diffusion: 0.2,
+            evaporation: 0.01,
+            eat_deposit: 50.0,
+            food_emission: 0.5,
+            values: vec![0.0; cols * rows],
+            buffer: vec![0.0; cols * rows],
+            cols,
+            rows,
+        }
+    }
+
+    /// Returns the cell (col, row) at `position`, positions outside the field use the
+    /// closest cell.
+    fn cell(&self, position: Vec2) -> (usize, usize) {
+        (
+            ((position.x / SCENT_CELL_SIZE).max(0.0) as usize).min(self.cols - 1),
+            ((position.y / SCENT_CELL_SIZE).max(0.0) as usize).min(self.rows - 1),
+        )
+    }
+
+    /// Gets the value at (col, row) wrapping around the field borders.
+    fn at(&self, col: isize, row: isize) -> f32 {
+        let col = col.rem_euclid(self.cols as isize) as usize;
+        let row = row.rem_euclid(self.rows as isize) as usize;
+        self.values[row * self.cols + col]
+    }
+
+    /// Get the scent value at `position`.
+    pub fn value(&self, position: Vec2) -> f32 {
+        let (col, row) = self.cell(position);
+        self.values[row * self.cols + col]
+    }
+
+    pub fn deposit(&mut self, position: Vec2, amount: f32) {
+        if self.enabled {
+            let (col, row) = self.cell(position);
+            self.values[row * self.cols + col] += amount;
+        }
+    }
+
+    /// Get the scent gradient at `position`, it points to where the scent increases.
+    pub fn gradient(&self, position: Vec2) -> Vec2 {
+        let (col, row) = self.cell(position);
+        let (col, row) = (col as isize, row as isize);
+        vec2(
+            self.at(col + 1, row) - self.at(col - 1, row),
+            self.at(col, row + 1) - self.at(col, row - 1),
+        ) / 2.0
+    }
+
+    pub fn clear(&mut self) {
+        self.values.iter_mut().for_each(|v| *v = 0.0);
+    }
+
+    /// 1. Food emits scent.
+    /// 2. Scent diffuses to the neighbour cells.
+    /// 3. Scent evaporates.
+    pub fn update_step(&mut self, foods: &[Food]) {
+        if !self.enabled {
+            return;
+        }
+        for food in foods {
+            self.deposit(food.position, self.food_emission);
+        }
+        let keep = (1.0 - self.diffusion) * (1.0 - self.evaporation);
+        let spread = self.diffusion * (1.0 - self.evaporation) / 4.0;
+        for row in 0..self.rows as isize {
+            for col in 0..self.cols as isize {
+                let neighbours = self.at(col - 1, row)
+                    + self.at(col + 1, row)
+                    + self.at(col, row - 1)
+                    + self.at(col, row + 1);
+                self.buffer[row as usize * self.cols + col as usize] =
+                    self.at(col, row) * keep + neighbours * spread;
+            }
+        }
+        std::mem::swap(&mut self.values, &mut self.buffer);
+    }
+
+    pub fn draw(&self) {
+        if !(self.enabled && self.visible) {
+            return;
+        }
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                let value = self.values[row * self.cols + col];
+                if value > 1e-2 {
+                    let alpha = (value / SCENT_DRAW_MAX).min(1.0) * 0.6;
+                    draw_rectangle(
+                        col as f32 * SCENT_CELL_SIZE,
+                        row as f32 * SCENT_CELL_SIZE,
+                        SCENT_CELL_SIZE,
+                        SCENT_CELL_SIZE,
+                        Color::new(0.9, 0.8, 0.2, alpha),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffusion_works() {
+        let mut field = ScentField::new(vec2(100.0, 100.0));
+        field.enabled = true;
+        let center = vec2(55.0, 55.0);
+        field.deposit(center, 10.0);
+        field.update_step(&[]);
+        let right = center + vec2(SCENT_CELL_SIZE, 0.0);
+        assert!(field.value(right) > 0.0);
+        assert!(field.value(center) < 10.0);
+        let total: f32 = field.values.iter().sum();
+        assert!(total < 10.0);
+        // Gradient points to the deposit
+        let gradient = field.gradient(right + vec2(SCENT_CELL_SIZE, 0.0));
+        assert!(gradient.x < 0.0);
+    }
+
+    #[test]
+    fn disabled_field_is_empty() {
+        let mut field = ScentField::new(vec2(100.0, 100.0));
+        field.deposit(vec2(55.0, 55.0), 10.0);
+        assert_eq!(field.value(vec2(55.0, 55.0)), 0.0);
+    }
+}
@@ -0,0 +1,415 @@
+//! # Simulation.
+//!
+//! Headless core of the world: the entities, their controllers and the update step,
+//! without any drawing or UI so it can also run in batch.
+use crate::{
+    biome::BiomeMap,
+    event::{Event, EventSchedule},
+    food::FoodController,
+    heatmap::Heatmaps,
+    obstacle::ObstacleMap,
+    replay::Action,
+    rng,
+    scenario::Scenario,
+    scent::ScentField,
+    slime::SlimeController,
+    stats::{Stats, StatsRecorder},
+    utils::random_position,
+};
+use macroquad::prelude::*;
+
+/// World size used when the scenario doesn't set one.
+pub const DEFAULT_WORLD_SIZE: Vec2 = glam::const_vec2!([800.0, 600.0]);
+
+/// State of the simulation at some time, restoring it runs the same steps again.
+/// Obstacles and biomes are not included.
+#[derive(Clone)]
+pub struct Snapshot {
+    pub time: f32,
+    food_controller: FoodController,
+    slime_controller: SlimeController,
+    scent: ScentField,
+    events: EventSchedule,
+    stopped: Option<String>,
+    rng_state: u64,
+}
+
+pub struct Simulation {
+    pub world_size: Vec2,
+    pub food_controller: FoodController,
+    pub slime_controller: SlimeController,
+    pub obstacles: ObstacleMap,
+    pub biomes: BiomeMap,
+    pub scent: ScentField,
+    pub stats_recorder: StatsRecorder,
+    /// Disabled by default, as headless runs rarely need them.
+    pub heatmaps: Heatmaps,
+    pub events: EventSchedule,
+    /// Scenario the simulation was built from, provides the seed, initial entities and
+    /// stop conditions.
+    pub scenario: Scenario,
+    pub time: f32,
+    /// Reason why the simulation stopped.
+    pub stopped: Option<String>,
+    /// State of the random generator, kept apart from the thread one so simulations
+    /// sharing a thread don't affect each other.
+    rng_state: u64,
+}
+
+impl Simulation {
+    /// Builds the simulation from `scenario`, `world_size` is used when the scenario
+    /// doesn't set one.
+    pub fn new(scenario: Scenario, world_size: Vec2) -> Result<Self, String> {
+        let world_size = scenario.world_size.unwrap_or(world_size);
+        let seed = scenario.seed.unwrap_or_else(|| rng::rand() as u64);
+        let thread_state = rng::state();
+        rng::srand(seed);
+        let biomes = scenario.biome_map(world_size);
+        let rng_state = rng::state();
+        rng::set_state(thread_state);
+        let mut simulation = Self {
+            world_size,
+            food_controller: scenario.food_controller(world_size),
+            slime_controller: scenario.slime_controller(world_size),
+            obstacles: scenario.obstacle_map(),
+            biomes: biomes?,
+            scent: scenario.scent_field(world_size),
+            stats_recorder: StatsRecorder::new(100.0),
+            heatmaps: Heatmaps::new(world_size),
+            events: scenario.event_schedule(),
+            scenario,
+            time: 0.0,
+            stopped: None,
+            rng_state,
+        };
+        simulation.reset();
+        Ok(simulation)
+    }
+
+    /// Builds a scenario from the current settings.
+    pub fn scenario(&self) -> Scenario {
+        let mut scenario = Scenario {
+            world_size: Some(self.world_size),
+            slime: self.slime_controller.config.clone(),
+            slimes: (&self.slime_controller).into(),
+            food: (&self.food_controller).into(),
+            scent: (&self.scent).into(),
+            obstacles: self.obstacles.obstacles.clone(),
+            events: self.events.events.clone(),
+            ..self.scenario.clone()
+        };
+        scenario.biomes.enabled = self.biomes.enabled;
+        scenario.biomes.modifiers = self.biomes.modifiers;
+        scenario
+    }
+
+    /// Runs `f` using the simulation random generator.
+    fn with_rng<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let thread_state = rng::state();
+        rng::set_state(self.rng_state);
+        let result = f(self);
+        self.rng_state = rng::state();
+        rng::set_state(thread_state);
+        result
+    }
+
+    /// Advances one time step and checks the stop conditions.
+    pub fn step(&mut self) {
+        self.with_rng(Self::update);
+    }
+
+    fn update(&mut self) {
+        self.food_controller.set_time(self.time);
+        self.slime_controller.set_time(self.time);
+        for event in self.events.check_events(self.time) {
+            self.handle_event(&event);
+        }
+        self.food_controller
+            .update_step(&self.obstacles, &self.biomes);
+        self.slime_controller.update_step(
+            &mut self.food_controller.population,
+            &self.obstacles,
+            &self.biomes,
+            &mut self.scent,
+        );
+        self.scent.update_step(&self.food_controller.population);
+        self.heatmaps.record(
+            self.time,
+            &self.slime_controller.population.position,
+            &self.slime_controller.activity,
+        );
+        self.slime_controller.activity.clear();
+        self.stats_recorder.check_record(
+            self.time,
+            &self.slime_controller.population,
+            &self.food_controller.population,
+        );
+        self.time += 1.0;
+        self.stopped = self
+            .scenario
+            .stop
+            .check(self.time, self.slime_controller.population.len());
+    }
+
+    pub fn apply_event(&mut self, event: &Event) {
+        self.with_rng(|simulation| simulation.handle_event(event));
+    }
+
+    /// Applies an intervention, random numbers come from the simulation generator so
+    /// replaying the same actions gives the same run.
+    pub fn apply_action(&mut self, action: &Action) -> Result<(), String> {
+        match action {
+            Action::Settings { fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|(field, value)| (field.clone(), value.clone()))
+                    .collect::<Vec<_>>();
+                let scenario = self.scenario().with_fields(&fields)?;
+                self.apply_settings(scenario);
+            }
+            Action::Reset => self.reset(),
+            Action::SpawnFood => self.with_rng(|simulation| {
+                simulation
+                    .food_controller
+                    .spawn_one(&simulation.obstacles, &simulation.biomes)
+            }),
+            Action::SpawnSlime => self.with_rng(|simulation| {
+                simulation.slime_controller.spawn_one(&simulation.obstacles)
+            }),
+            Action::InfectSlime => {
+                self.with_rng(|simulation| simulation.slime_controller.infect_random(1))
+            }
+            Action::GenerateBiomes => self.with_rng(|simulation| {
+                let enabled = simulation.biomes.enabled;
+                simulation.biomes = BiomeMap::generate(simulation.world_size);
+                simulation.biomes.enabled = enabled;
+            }),
+            Action::Event { event } => self.apply_event(event),
+        }
+        Ok(())
+    }
+
+    /// Sets the settings of the running simulation from `scenario`, the entities and
+    /// the event schedule are kept.
+    pub fn apply_settings(&mut self, scenario: Scenario) {
+        scenario.food.apply(&mut self.food_controller);
+        scenario.slimes.apply(&mut self.slime_controller);
+        self.slime_controller.config = scenario.slime.clone();
+        scenario.scent.apply(&mut self.scent);
+        self.biomes.enabled = scenario.biomes.enabled;
+        self.biomes.modifiers = scenario.biomes.modifiers;
+        self.obstacles.obstacles = scenario.obstacles.clone();
+        self.scenario = scenario;
+    }
+
+    fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::Famine { duration } => self.food_controller.start_famine(*duration),
+            Event::Meteor { position, radius } => {
+                let position = position.unwrap_or_else(|| random_position(self.world_size));
+                self.slime_controller.kill_in_radius(position, *radius);
+            }
+            Event::Bloom { amount } => {
+                for _ in 0..*amount {
+                    self.food_controller
+                        .spawn_one(&self.obstacles, &self.biomes);
+                }
+            }
+            Event::Plague { infected } => {
+                self.slime_controller.disease.enabled = true;
+                self.slime_controller.infect_random(*infected);
+            }
+            Event::Migration { amount, skills } => {
+                self.slime_controller
+                    .spawn_with_skills(*amount, (*skills).into(), &self.obstacles);
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            time: self.time,
+            food_controller: self.food_controller.clone(),
+            slime_controller: self.slime_controller.clone(),
+            scent: self.scent.clone(),
+            events: self.events.clone(),
+            stopped: self.stopped.clone(),
+            rng_state: self.rng_state,
+        }
+    }
+
+    /// Goes back to the snapshot, including the settings of the entities at that time.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.time = snapshot.time;
+        self.food_controller = snapshot.food_controller.clone();
+        self.slime_controller = snapshot.slime_controller.clone();
+        self.scent = snapshot.scent.clone();
+        self.events = snapshot.events.clone();
+        self.stopped = snapshot.stopped.clone();
+        self.rng_state = snapshot.rng_state;
+        self.stats_recorder.truncate(snapshot.time);
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats::collect(
+            self.time,
+            &self.slime_controller.population,
+            &self.food_controller.population,
+        )
+    }
+
+    /// Resets simulation, seeded simulations start over with the same random numbers.
+    pub fn reset(&mut self) {
+        self.with_rng(Self::respawn);
+    }
+
+    fn respawn(&mut self) {
+        if let Some(seed) = self.scenario.seed {
+            rng::srand(seed);
+        }
+        self.food_controller.population.clear();
+        for &position in self.scenario.placed.food.iter() {
+            self.food_controller.spawn_at(position);
+        }
+        self.food_controller
+            .spawn_n(self.scenario.initial_food, &self.obstacles, &self.biomes);
+        self.food_controller.last_spawn_time = 0.0;
+        self.food_controller.famine_until = 0.0;
+        self.slime_controller.clear();
+        for slime in self.scenario.placed.slimes.iter() {
+            self.slime_controller
+                .spawn_at(slime.position, slime.skills.into());
+        }
+        self.slime_controller
+            .spawn_n(self.scenario.initial_slimes, &self.obstacles);
+        // Undo plagues
+        self.slime_controller.disease.enabled = self.scenario.slimes.disease.enabled;
+        self.scent.clear();
+        self.slime_controller.activity.clear();
+        self.heatmaps.clear();
+        self.stats_recorder.clear();
+        self.events.reset();
+        self.stopped = None;
+        self.time = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_is_reproducible() {
+        let scenario = Scenario {
+            seed: Some(3),
+            ..Scenario::default()
+        };
+        let run = || {
+            let mut simulation = Simulation::new(scenario.clone(), DEFAULT_WORLD_SIZE).unwrap();
+            (0..500).for_each(|_| simulation.step());
+            simulation.slime_controller.population.position
+        };
+        assert_eq!(run(), run());
+        // Interleaved simulations on the same thread don't affect each other
+        let mut a = Simulation::new(scenario.clone(), DEFAULT_WORLD_SIZE).unwrap();
+        let mut b = Simulation::new(Scenario::default(), DEFAULT_WORLD_SIZE).unwrap();
+        for _ in 0..500 {
+            a.step();
+            b.step();
+        }
+        assert_eq!(a.slime_controller.population.position, run());
+        // The number of threads doesn't change the result
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        assert_eq!(pool.install(run), run());
+    }
+
+    #[test]
+    fn update_step_is_deterministic() {
+        // Crowded world with every interaction between slimes enabled, slimes spawn
+        // ready to breed
+        let mut scenario = Scenario {
+            seed: Some(7),
+            world_size: Some(vec2(400.0, 400.0)),
+            initial_slimes: 300,
+            initial_food: 100,
+            ..Scenario::default()
+        };
+        let initial_energy = scenario.slime.initial_energy;
+        scenario.slime.initial_energy *= 3.0;
+        let slimes = &mut scenario.slimes;
+        slimes.collisions = true;
+        slimes.crowding_cost = 0.1;
+        slimes.behavior.flee_size_ratio = 1.2;
+        slimes.group.flocking = true;
+        slimes.group.kin_sharing = true;
+        slimes.disease.enabled = true;
+        scenario.scent.enabled = true;
+        let run = || {
+            let mut simulation = Simulation::new(scenario.clone(), DEFAULT_WORLD_SIZE).unwrap();
+            let controller = &mut simulation.slime_controller;
+            controller.config.initial_energy = initial_energy;
+            simulation.time = controller.breeding_cooldown;
+            simulation.with_rng(|simulation| simulation.slime_controller.infect_random(30));
+            (0..200).for_each(|_| simulation.step());
+            let population = &simulation.slime_controller.population;
+            (population.id.clone(), population.position.clone())
+        };
+        let run_with_threads = |threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(run)
+        };
+        let expected = run_with_threads(1);
+        assert!(
+            expected.0.iter().any(|&id| id >= 300),
+            "Slimes should breed"
+        );
+        assert_eq!(run_with_threads(2), expected);
+        assert_eq!(run_with_threads(4), expected);
+    }
+
+    #[test]
+    fn reset_undoes_plague() {
+        let scenario = Scenario {
+            seed: Some(4),
+            ..Scenario::default()
+        };
+        let mut simulation = Simulation::new(scenario.clone(), DEFAULT_WORLD_SIZE).unwrap();
+        simulation.handle_event(&Event::Plague { infected: 5 });
+        (0..100).for_each(|_| simulation.step());
+        simulation.reset();
+        assert!(!simulation.slime_controller.disease.enabled);
+        (0..100).for_each(|_| simulation.step());
+        let mut fresh = Simulation::new(scenario, DEFAULT_WORLD_SIZE).unwrap();
+        (0..100).for_each(|_| fresh.step());
+        assert_eq!(
+            simulation.slime_controller.population.position,
+            fresh.slime_controller.population.position
+        );
+    }
+
+    #[test]
+    fn restore_works() {
+        let scenario = Scenario {
+            seed: Some(5),
+            ..Scenario::default()
+        };
+        let mut simulation = Simulation::new(scenario, DEFAULT_WORLD_SIZE).unwrap();
+        (0..100).for_each(|_| simulation.step());
+        let snapshot = simulation.snapshot();
+        (0..300).for_each(|_| simulation.step());
+        let positions = simulation.slime_controller.population.position.clone();
+        let records = simulation.stats_recorder.records.len();
+        simulation.restore(&snapshot);
+        assert_eq!(simulation.time, 100.0);
+        assert_eq!(simulation.stats_recorder.records.len(), 1);
+        (0..300).for_each(|_| simulation.step());
+        assert_eq!(simulation.slime_controller.population.position, positions);
+        assert_eq!(simulation.stats_recorder.records.len(), records);
+    }
+}
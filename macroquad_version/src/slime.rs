@@ -1,10 +1,19 @@
 //! # Slime entity.
 #![doc = include_str!("../../docs/slime.md")]
 use crate::{
-    food::Food,
-    utils::{get_angle_direction, random_screen_position, wrap_around},
+    biome::{BiomeMap, BiomeModifiers},
+    disease::{DiseaseConfig, Health},
+    food::{Food, SPAWN_ATTEMPTS},
+    heatmap::Activity,
+    obstacle::ObstacleMap,
+    population::Population,
+    rng::{self, gen_range},
+    scent::ScentField,
+    utils::{get_angle_direction, random_position, wrap_around, wrapped_diff},
 };
-use macroquad::{prelude::*, rand::gen_range};
+use macroquad::prelude::*;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::f32::consts::PI;
 
 /// When slime is below this threshold, its free to move without energy cost.
@@ -20,8 +29,21 @@ const EVOLVE_REQUIREMENT: f32 = 50.0;
 /// Maximum number of skills.
 const EVOLVE_LIMIT: usize = 30;
 const SIZE_RANGE: (f32, f32) = (1.5, 50.0);
+/// Maximum change of the heritable traits inherited by a child.
+const TRAIT_MUTATION: f32 = 0.1;
+/// Chance per step of an idle slime with restfulness 1 to start resting.
+const REST_CHANCE: f32 = 0.01;
+/// Minimum perceived scent gradient (gradient * sensitivity) a slime will follow.
+const SCENT_THRESHOLD: f32 = 0.01;
+/// Spreads the slime ids over the seeds of each step.
+const SEED_SPREAD: u64 = 0x9E3779B97F4A7C15;
+/// Side of the cells of `FoodGrid`.
+const FOOD_CELL_SIZE: f32 = 40.0;
+/// Minimum side of the cells of `SlimeGrid`.
+const SLIME_CELL_SIZE: f32 = 40.0;
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SlimeConfig {
     pub initial_energy: f32,
     pub speed_factor: f32,
@@ -60,7 +82,59 @@ pub enum SlimeState {
     Breeding,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+/// What the slime is currently doing, updated every step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Behavior {
+    /// Nothing in sight, moves randomly turning a bit every step.
+    Wander,
+    /// Goes to the nearest food (or follows the scent trail).
+    Seek,
+    /// Runs away from a much bigger slime.
+    Flee,
+    /// Stays still, paying a reduced time cost.
+    Rest,
+    /// Goes to the nearest slime ready to breed.
+    Court,
+}
+
+impl Behavior {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Wander => "Wander",
+            Self::Seek => "Seek",
+            Self::Flee => "Flee",
+            Self::Rest => "Rest",
+            Self::Court => "Court",
+        }
+    }
+}
+
+/// Configurable rules for the behavior transitions.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BehaviorRules {
+    /// Maximum random turn (radians) per step while wandering.
+    pub wander_turn: f32,
+    /// Time steps a slime rests before wandering again.
+    pub rest_duration: f32,
+    /// Fraction of the time cost paid while resting.
+    pub rest_time_cost: f32,
+    /// Slimes flee from others in vision this times bigger (0 to disable).
+    pub flee_size_ratio: f32,
+}
+
+impl Default for BehaviorRules {
+    fn default() -> Self {
+        Self {
+            wander_turn: 0.15,
+            rest_duration: 200.0,
+            rest_time_cost: 0.5,
+            flee_size_ratio: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SkillType {
     /// Increase the range of vision to detect food and increases a bit the speed.
     Vision,
@@ -71,6 +145,17 @@ pub enum SkillType {
 }
 
 impl SkillType {
+    pub const ALL: [SkillType; 3] = [SkillType::Vision, SkillType::Efficiency, SkillType::Jumper];
+
+    /// Color used for the skill in the UI.
+    pub fn color(&self) -> Color {
+        match self {
+            Self::Vision => ORANGE,
+            Self::Efficiency => PURPLE,
+            Self::Jumper => PINK,
+        }
+    }
+
     fn random() -> Self {
         match gen_range(0, 3) {
             0 => Self::Vision,
@@ -165,44 +250,320 @@ impl Skills {
     }
 }
 
+/// Heritable traits in the range [0, 1], children get the parents average with a
+/// small mutation.
+#[derive(Clone, Debug)]
+pub struct Traits {
+    /// Scales the perceived scent gradient.
+    pub scent_sensitivity: f32,
+    /// Chance to rest when idle.
+    pub restfulness: f32,
+    /// Reduces the chance of getting infected.
+    pub resistance: f32,
+    /// Flocking weight to move towards the center of nearby slimes.
+    pub cohesion: f32,
+    /// Flocking weight to move in the same direction as nearby slimes.
+    pub alignment: f32,
+    /// Flocking weight to keep away from nearby slimes.
+    pub separation: f32,
+    /// Fraction of `GroupRules::share_energy` given to starving kin.
+    pub generosity: f32,
+}
+
+impl Traits {
+    fn random() -> Self {
+        Self {
+            scent_sensitivity: gen_range(0.0, 1.0),
+            restfulness: gen_range(0.0, 1.0),
+            resistance: gen_range(0.0, 1.0),
+            cohesion: gen_range(0.0, 1.0),
+            alignment: gen_range(0.0, 1.0),
+            separation: gen_range(0.0, 1.0),
+            generosity: gen_range(0.0, 1.0),
+        }
+    }
+
+    fn inherit(&self, other: &Self) -> Self {
+        Self {
+            scent_sensitivity: inherit_trait(self.scent_sensitivity, other.scent_sensitivity),
+            restfulness: inherit_trait(self.restfulness, other.restfulness),
+            resistance: inherit_trait(self.resistance, other.resistance),
+            cohesion: inherit_trait(self.cohesion, other.cohesion),
+            alignment: inherit_trait(self.alignment, other.alignment),
+            separation: inherit_trait(self.separation, other.separation),
+            generosity: inherit_trait(self.generosity, other.generosity),
+        }
+    }
+}
+
+/// Average of the parents traits with a random mutation, clamped to [0, 1].
+fn inherit_trait(a: f32, b: f32) -> f32 {
+    ((a + b) / 2.0 + gen_range(-TRAIT_MUTATION, TRAIT_MUTATION)).clamp(0.0, 1.0)
+}
+
+/// A single slime, the `SlimeController` keeps them in a `Population`.
 #[derive(Clone)]
 pub struct Slime {
+    /// Unique identifier given by the `SlimeController`.
+    pub id: usize,
+    /// Ids of the parents, `None` for spawned slimes.
+    pub parents: Option<(usize, usize)>,
     pub position: Vec2,
     pub state: SlimeState,
     pub skills: Skills,
-    pub config: SlimeConfig,
-    speed: Vec2,
-    energy: f32,
-    size: f32,
-    last_jump: f32,
-    last_breed: f32,
-    next_skill_goal: f32,
-    skill_path: SkillType,
+    /// Modifiers of the biome where the slime is standing.
+    pub biome: BiomeModifiers,
+    pub traits: Traits,
+    pub health: Health,
+    pub behavior: Behavior,
+    /// Time when it was added to the world.
+    pub born: f32,
+    /// Number of children it had.
+    pub children: usize,
+    /// Id of the spawned slime that started its lineage (following the first parent).
+    pub founder: usize,
+    /// Number of ancestors in the longest line to a spawned slime.
+    pub generation: usize,
+    pub(crate) rest_until: f32,
+    pub(crate) speed: Vec2,
+    pub(crate) energy: f32,
+    pub(crate) size: f32,
+    pub(crate) last_jump: f32,
+    pub(crate) last_breed: f32,
+    pub(crate) next_skill_goal: f32,
+    pub(crate) skill_path: SkillType,
+}
+
+/// Size proportional to the energy.
+fn energy_size(energy: f32) -> f32 {
+    (energy / 50.0).clamp(SIZE_RANGE.0, SIZE_RANGE.1)
+}
+
+/// Checks the nearest position to `position` and returns its index and distance.
+fn nearest_position(position: Vec2, positions: impl Iterator<Item = Vec2>) -> Option<(usize, f32)> {
+    positions
+        .enumerate()
+        .map(|(i, pos)| (i, position.distance(pos)))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+}
+
+/// Sorts the `(index, cell, position)` entries by cell keeping their order inside each
+/// cell. Returns the start of each cell in the sorted entries (the last one is the
+/// number of entries) and the index and position of each entry.
+fn sort_by_cell(
+    entries: &[(usize, usize, Vec2)],
+    cells: usize,
+) -> (Vec<usize>, Vec<(usize, Vec2)>) {
+    let mut starts = vec![0; cells + 1];
+    for &(_, cell, _) in entries.iter() {
+        starts[cell + 1] += 1;
+    }
+    for cell in 0..cells {
+        starts[cell + 1] += starts[cell];
+    }
+    let mut next = starts.clone();
+    let mut sorted = vec![(0, Vec2::ZERO); entries.len()];
+    for &(i, cell, position) in entries.iter() {
+        sorted[next[cell]] = (i, position);
+        next[cell] += 1;
+    }
+    (starts, sorted)
+}
+
+/// Food positions bucketed in square cells, so searches only visit the cells around a
+/// slime instead of every food.
+struct FoodGrid {
+    positions: Vec<Vec2>,
+    cols: usize,
+    rows: usize,
+    /// Start of each cell in `foods`, the last one is the number of foods.
+    starts: Vec<usize>,
+    /// Index and position of every food sorted by cell, then by index. Food outside the
+    /// world goes to the closest cell.
+    foods: Vec<(usize, Vec2)>,
+}
+
+impl FoodGrid {
+    fn new(foods: &[Food], world_size: Vec2) -> Self {
+        let cols = (world_size.x / FOOD_CELL_SIZE).ceil().max(1.0) as usize;
+        let rows = (world_size.y / FOOD_CELL_SIZE).ceil().max(1.0) as usize;
+        let mut grid = Self {
+            positions: foods.iter().map(|f| f.position).collect(),
+            cols,
+            rows,
+            starts: Vec::new(),
+            foods: Vec::new(),
+        };
+        let entries = grid
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(i, &position)| {
+                let (col, row) = grid.cell(position);
+                (i, row * cols + col, position)
+            })
+            .collect::<Vec<_>>();
+        (grid.starts, grid.foods) = sort_by_cell(&entries, cols * rows);
+        grid
+    }
+
+    fn cell(&self, position: Vec2) -> (usize, usize) {
+        let cell = position / FOOD_CELL_SIZE;
+        (
+            (cell.x.max(0.0) as usize).min(self.cols - 1),
+            (cell.y.max(0.0) as usize).min(self.rows - 1),
+        )
+    }
+
+    /// Index, position and distance of the foods at most `radius` away from `position`.
+    fn nearby(&self, position: Vec2, radius: f32) -> impl Iterator<Item = (usize, Vec2, f32)> + '_ {
+        let (min_col, min_row) = self.cell(position - Vec2::splat(radius));
+        let (max_col, max_row) = self.cell(position + Vec2::splat(radius));
+        (min_row..=max_row)
+            .flat_map(move |row| (min_col..=max_col).map(move |col| row * self.cols + col))
+            .flat_map(move |cell| self.foods[self.starts[cell]..self.starts[cell + 1]].iter())
+            .map(move |&(i, food)| (i, food, position.distance(food)))
+            .filter(move |&(_, _, distance)| distance <= radius)
+    }
+
+    /// Index and distance of the foods at most `radius` away from `position`.
+    fn within(&self, position: Vec2, radius: f32) -> impl Iterator<Item = (usize, f32)> + '_ {
+        self.nearby(position, radius)
+            .map(|(i, _, distance)| (i, distance))
+    }
+
+    /// Index and distance of the nearest food in line of sight at most `radius` away,
+    /// ties go to the lowest index.
+    fn nearest(
+        &self,
+        position: Vec2,
+        radius: f32,
+        obstacles: &ObstacleMap,
+    ) -> Option<(usize, f32)> {
+        self.nearby(position, radius)
+            .filter(|&(_, food, _)| obstacles.is_visible(position, food))
+            .map(|(i, _, distance)| (i, distance))
+            .min_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)))
+    }
+}
+
+/// Slime indices and positions bucketed in cells that evenly split the world, so
+/// neighbour searches only visit the cells around a slime and can wrap around the world
+/// edges. The slimes of each cell are stored next to each other, so the searches read
+/// contiguous memory instead of jumping around the population arrays.
+struct SlimeGrid {
+    cols: usize,
+    rows: usize,
+    cell_size: Vec2,
+    /// Size of the biggest slime.
+    max_size: f32,
+    /// Start of each cell in `slimes`, the last one is the number of slimes.
+    starts: Vec<usize>,
+    /// Index and position of every slime sorted by cell, then by index.
+    slimes: Vec<(usize, Vec2)>,
+}
+
+impl SlimeGrid {
+    /// Cells are at least `SLIME_CELL_SIZE` and two of the biggest slimes wide, so the
+    /// slimes overlapping another one are in its cell or the neighbouring ones.
+    fn new(population: &Population, world_size: Vec2) -> Self {
+        Self::filtered(population, world_size, |_| true)
+    }
+
+    /// Grid with only the slimes for which `keep` returns true, the cells are as wide
+    /// as in a grid with every slime.
+    fn filtered(population: &Population, world_size: Vec2, keep: impl Fn(usize) -> bool) -> Self {
+        let max_size = population.size.iter().cloned().fold(0.0, f32::max);
+        let min_cell_size = SLIME_CELL_SIZE.max(2.0 * max_size);
+        let cols = ((world_size.x / min_cell_size) as usize).max(1);
+        let rows = ((world_size.y / min_cell_size) as usize).max(1);
+        let cell_size = world_size / vec2(cols as f32, rows as f32);
+        let entries = (0..population.len())
+            .filter(|&i| keep(i))
+            .map(|i| {
+                let position = population.position[i];
+                let cell = position / cell_size;
+                let col = (cell.x.max(0.0) as usize).min(cols - 1);
+                let row = (cell.y.max(0.0) as usize).min(rows - 1);
+                (i, row * cols + col, position)
+            })
+            .collect::<Vec<_>>();
+        let (starts, slimes) = sort_by_cell(&entries, cols * rows);
+        Self {
+            cols,
+            rows,
+            cell_size,
+            max_size,
+            starts,
+            slimes,
+        }
+    }
+
+    /// Index and position (when the grid was built) of the slimes in the cells touching
+    /// the square of half side `radius` around `position`, wrapping around the world
+    /// edges. Each slime is listed once.
+    fn candidates(&self, position: Vec2, radius: f32) -> impl Iterator<Item = (usize, Vec2)> + '_ {
+        let min = ((position - Vec2::splat(radius)) / self.cell_size).floor();
+        let max = ((position + Vec2::splat(radius)) / self.cell_size).floor();
+        let cols = wrapped_range(min.x as i64, max.x as i64, self.cols);
+        let rows = wrapped_range(min.y as i64, max.y as i64, self.rows);
+        rows.flat_map(move |row| cols.clone().map(move |col| row * self.cols + col))
+            .flat_map(move |cell| {
+                self.slimes[self.starts[cell]..self.starts[cell + 1]]
+                    .iter()
+                    .copied()
+            })
+    }
+}
+
+/// Indices from `min` to `max` wrapped to `0..len`, each one at most once.
+fn wrapped_range(min: i64, max: i64, len: usize) -> impl Iterator<Item = usize> + Clone {
+    let len = len as i64;
+    let (min, max) = if max - min + 1 >= len {
+        (0, len - 1)
+    } else {
+        (min, max)
+    };
+    (min..=max).map(move |i| i.rem_euclid(len) as usize)
 }
 
 impl Slime {
-    pub fn new(position: Vec2, config: SlimeConfig) -> Self {
+    pub fn new(position: Vec2, config: &SlimeConfig) -> Self {
         let direction = gen_range(0.0, PI * 2.0);
         let speed = polar_to_cartesian(config.speed_factor, direction);
-        let mut slime = Self {
+        Self {
+            id: 0,
+            parents: None,
             position,
             state: SlimeState::Normal,
             skills: Skills::new(),
             speed,
             energy: config.initial_energy,
-            config,
-            size: 0.0,
+            biome: BiomeModifiers::default(),
+            traits: Traits::random(),
+            health: Health::Susceptible,
+            behavior: Behavior::Wander,
+            born: 0.0,
+            children: 0,
+            founder: 0,
+            generation: 0,
+            rest_until: 0.0,
+            size: energy_size(config.initial_energy),
             last_jump: 0.0,
             last_breed: 0.0,
             next_skill_goal: EVOLVE_REQUIREMENT,
             skill_path: SkillType::random(),
-        };
-        slime.update_size();
-        slime
+        }
     }
 
-    pub fn spawn(config: SlimeConfig) -> Self {
-        Self::new(random_screen_position(), config)
+    /// Spawns a slime at a random position outside the obstacles, none if no position
+    /// was found in `SPAWN_ATTEMPTS` tries.
+    pub fn spawn(config: &SlimeConfig, world_size: Vec2, obstacles: &ObstacleMap) -> Option<Self> {
+        let size = energy_size(config.initial_energy);
+        (0..SPAWN_ATTEMPTS)
+            .map(|_| random_position(world_size))
+            .find(|&p| !obstacles.contains(p, size))
+            .map(|position| Self::new(position, config))
     }
 
     /// Get the slime's size.
@@ -210,180 +571,261 @@ impl Slime {
         self.size
     }
 
-    /// Get the slime's speed factor considering skill modifications and size reduction
-    /// (the bigger, the slower).
-    pub fn speed_factor(&self, max_size_slow: f32) -> f32 {
-        let size_slower = 1.0 - (self.size * max_size_slow / SIZE_RANGE.1);
-        self.config.speed_factor
-            * (1.0
-                + (self.skills.vision as f32) / (EVOLVE_LIMIT as f32) * self.config.vision_skill
-                    / 2.5)
-            * size_slower
-    }
-
-    /// Get the slime's vision range considering skill modifications.
-    /// Max skill augmentation will increment it to 5x.
-    pub fn vision_range(&self) -> f32 {
-        self.config.vision_range
-            * (1.0 + (self.skills.vision as f32) / (EVOLVE_LIMIT as f32) * self.config.vision_skill)
+    /// Sets the skills and the energy needed for the next evolution.
+    pub fn set_skills(&mut self, skills: Skills) {
+        self.skills = skills;
+        self.next_skill_goal = if self.skills.count_levels() >= EVOLVE_LIMIT {
+            f32::MAX
+        } else {
+            (self.skills.count_levels() + 1) as f32 * EVOLVE_REQUIREMENT
+        };
     }
+}
 
-    pub fn size_vision(&self) -> f32 {
-        self.size + self.vision_range()
+/// Slime rules, `idx` is the index of the slime in the population and `config` the
+/// shared configuration of the `SlimeController`.
+impl Population {
+    /// Get the slime's energy.
+    pub fn energy(&self, idx: usize) -> f32 {
+        self.energy[idx]
     }
 
-    /// Set size as proportional to its energy.
-    pub fn update_size(&mut self) {
-        self.size = (self.energy / 50.0).clamp(SIZE_RANGE.0, SIZE_RANGE.1);
+    /// Get the slime's size.
+    pub fn size(&self, idx: usize) -> f32 {
+        self.size[idx]
     }
 
-    /// Checks the nearst position and returns its index and distance.
-    fn nearest_position(&self, positions: impl Iterator<Item = Vec2>) -> Option<(usize, f32)> {
-        positions
-            .enumerate()
-            .map(|(i, pos)| (i, self.position.distance(pos)))
-            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+    /// Get the skill the slime is evolving.
+    pub fn skill_path(&self, idx: usize) -> SkillType {
+        self.skill_path[idx]
     }
 
-    /// Checks the nearest food and returns its index and distance.
-    fn nearest_food(&self, foods: &[Food]) -> Option<(usize, f32)> {
-        self.nearest_position(foods.iter().map(|f| f.position))
+    fn add_energy(&mut self, idx: usize, energy: f32) {
+        self.set_energy(idx, self.energy[idx] + energy);
     }
 
-    /// Checks the nearest other slime able to breed and returns its index and distance.
-    /// * `idx` - Index of the current slime in `slimes`.
-    fn nearest_breeding_slime(
-        &self,
-        idx: usize,
-        slimes: &[Slime],
-        time: f32,
-        breeding_cooldown: f32,
-    ) -> Option<(usize, f32)> {
-        let (idxs, positions): (Vec<_>, Vec<_>) = slimes
-            .iter()
-            .enumerate()
-            .filter_map(|(i, s)| {
-                if (i != idx) && (s.is_breed_ready(time, breeding_cooldown)) {
-                    Some((i, s.position))
-                } else {
-                    None
-                }
-            })
-            .unzip();
-
-        if let Some((i, distance)) = self.nearest_position(positions.into_iter()) {
-            Some((idxs[i], distance))
-        } else {
-            None
-        }
+    /// Sets the energy and the size proportional to it.
+    fn set_energy(&mut self, idx: usize, energy: f32) {
+        self.energy[idx] = energy;
+        self.size[idx] = energy_size(energy);
     }
 
-    /// Returns if point is inside the Slime
-    pub fn is_point_inside(&self, point: Vec2, padding: f32) -> bool {
-        self.position.distance(point) <= (self.size + padding)
+    /// Get the slime's speed factor considering skill modifications, biome and size
+    /// reduction (the bigger, the slower).
+    pub fn speed_factor(&self, idx: usize, config: &SlimeConfig, max_size_slow: f32) -> f32 {
+        let size_slower = 1.0 - (self.size[idx] * max_size_slow / SIZE_RANGE.1);
+        config.speed_factor
+            * self.biome[idx].speed
+            * (1.0
+                + (self.skills[idx].vision as f32) / (EVOLVE_LIMIT as f32) * config.vision_skill
+                    / 2.5)
+            * size_slower
     }
 
-    /// Get the slime's energy.
-    pub fn energy(&self) -> f32 {
-        self.energy
+    /// Get the slime's vision range considering skill modifications and biome.
+    /// Max skill augmentation will increment it to 5x.
+    pub fn vision_range(&self, idx: usize, config: &SlimeConfig) -> f32 {
+        config.vision_range
+            * self.biome[idx].vision
+            * (1.0 + (self.skills[idx].vision as f32) / (EVOLVE_LIMIT as f32) * config.vision_skill)
     }
 
-    fn add_energy(&mut self, energy: f32) {
-        self.energy += energy;
-        self.update_size();
+    pub fn size_vision(&self, idx: usize, config: &SlimeConfig) -> f32 {
+        self.size[idx] + self.vision_range(idx, config)
     }
 
-    /// Get the slime's step cost considering skill modifications.
+    /// Get the slime's step cost considering skill modifications and biome.
     /// Max skill augmentation will decrease it by 1/3.
-    pub fn step_cost(&self) -> f32 {
-        self.config.step_cost
+    pub fn step_cost(&self, idx: usize, config: &SlimeConfig) -> f32 {
+        config.step_cost * self.biome[idx].step_cost
             / (1.0
-                + (self.skills.efficiency as f32) / (EVOLVE_LIMIT as f32)
-                    * self.config.efficiency_skill)
-    }
-
-    fn apply_movement_cost(&mut self) {
-        if self.energy > FREE_MOVEMENT_TH {
-            let mult = (self.energy / 100.0).max(1.0);
-            self.add_energy(-self.step_cost() * mult);
-        }
-    }
-
-    /// Move 1 step
-    fn move_step(&mut self) {
-        self.position += self.speed;
-        self.position = wrap_around(&self.position);
-        self.apply_movement_cost();
+                + (self.skills[idx].efficiency as f32) / (EVOLVE_LIMIT as f32)
+                    * config.efficiency_skill)
     }
 
     /// Get the slime's jump cooldown considering skill modifications.
     /// Max skill augmentation will decrease it by 1/5.
-    pub fn jump_cooldown(&self) -> f32 {
-        self.config.jump_cooldown
-            / (1.0 + (self.skills.jumper as f32) / (EVOLVE_LIMIT as f32) * self.config.jumper_skill)
+    pub fn jump_cooldown(&self, idx: usize, config: &SlimeConfig) -> f32 {
+        config.jump_cooldown
+            / (1.0 + (self.skills[idx].jumper as f32) / (EVOLVE_LIMIT as f32) * config.jumper_skill)
     }
 
-    fn is_jump_ready(&self, time: f32) -> bool {
-        (self.energy >= JUMP_REQUIREMENT) && ((time - self.last_jump) >= self.jump_cooldown())
+    pub fn is_jump_ready(&self, idx: usize, time: f32, config: &SlimeConfig) -> bool {
+        (self.energy[idx] >= JUMP_REQUIREMENT)
+            && ((time - self.last_jump[idx]) >= self.jump_cooldown(idx, config))
     }
 
-    fn jump_distance(&self) -> f32 {
+    pub fn jump_distance(&self, idx: usize, config: &SlimeConfig) -> f32 {
         JUMP_DISTANCE
-            * (1.0 + (self.skills.jumper as f32) / (EVOLVE_LIMIT as f32) * self.config.jumper_skill)
+            * (1.0 + (self.skills[idx].jumper as f32) / (EVOLVE_LIMIT as f32) * config.jumper_skill)
             / 9.0
     }
 
     /// Needs to have at least 2.5 times the initial energy.
-    pub fn is_breed_ready(&self, time: f32, breeding_cooldown: f32) -> bool {
-        (self.state != SlimeState::Breeding)
-            && (self.energy >= self.config.initial_energy * 2.5)
-            && ((time - self.last_breed) >= breeding_cooldown)
+    pub fn is_breed_ready(
+        &self,
+        idx: usize,
+        time: f32,
+        breeding_cooldown: f32,
+        config: &SlimeConfig,
+    ) -> bool {
+        (self.state[idx] != SlimeState::Breeding)
+            && (self.energy[idx] >= config.initial_energy * 2.5)
+            && ((time - self.last_breed[idx]) >= breeding_cooldown)
+    }
+
+    /// Returns if point is inside the Slime
+    pub fn is_point_inside(&self, idx: usize, point: Vec2, padding: f32) -> bool {
+        self.position[idx].distance(point) <= (self.size[idx] + padding)
+    }
+
+    /// Returns if both slimes are parent and child or siblings.
+    pub fn is_kin(&self, a: usize, b: usize) -> bool {
+        let is_parent_of = |parent: usize, child: usize| {
+            self.parents[child]
+                .is_some_and(|(a, b)| (a == self.id[parent]) || (b == self.id[parent]))
+        };
+        let are_siblings = match (self.parents[a], self.parents[b]) {
+            (Some((a, b)), Some((c, d))) => (a == c) || (a == d) || (b == c) || (b == d),
+            _ => false,
+        };
+        is_parent_of(a, b) || is_parent_of(b, a) || are_siblings
     }
 
-    fn is_evolve_ready(&self) -> bool {
-        self.energy >= self.next_skill_goal
+    /// Returns the displacements needed for the slimes `a` and `b` to stop overlapping,
+    /// each one moves proportionally to the other's size (bigger slimes push harder).
+    fn collision_push(&self, a: usize, b: usize, world_size: Vec2) -> Option<(Vec2, Vec2)> {
+        let diff = wrapped_diff(self.position[a], self.position[b], world_size);
+        let distance = diff.length();
+        let total_size = self.size[a] + self.size[b];
+        let overlap = total_size - distance;
+        if overlap <= 0.0 {
+            return None;
+        }
+        let normal = if distance > 0.0 {
+            diff / distance
+        } else {
+            Vec2::X
+        };
+        Some((
+            -normal * overlap * (self.size[b] / total_size),
+            normal * overlap * (self.size[a] / total_size),
+        ))
     }
 
     /// Returns a new `Slime` with an initial energy. It will randomly inherit one skill
-    /// from each parent at random reducing its level by 2..=10 (rounded up).
-    fn breed(&mut self, partner: &mut Self, energy: f32, time: f32) -> Self {
-        self.last_breed = time;
-        self.state = SlimeState::Breeding;
-        self.add_energy(-energy);
-        partner.last_breed = time;
-        partner.state = SlimeState::Breeding;
-        partner.add_energy(-energy);
-        let mut child = Self::new(self.position, self.config.clone());
-        let skills = match (self.skills.inherit(), partner.skills.inherit()) {
+    /// from each parent at random reducing its level by 2..=10 (rounded up), and the
+    /// parents average traits with a small mutation.
+    fn breed(&mut self, a: usize, b: usize, energy: f32, time: f32, config: &SlimeConfig) -> Slime {
+        for i in [a, b] {
+            self.last_breed[i] = time;
+            self.state[i] = SlimeState::Breeding;
+            self.children[i] += 1;
+            self.add_energy(i, -energy);
+        }
+        let mut child = Slime::new(self.position[a], config);
+        let skills = match (self.skills[a].inherit(), self.skills[b].inherit()) {
             (None, None) => Skills::new(),
             (None, Some(s)) => s,
             (Some(s), None) => s,
             (Some(sa), Some(sb)) => sa.merge(sb),
         };
-        child.skills = skills;
-        child.next_skill_goal = if child.skills.count_levels() == EVOLVE_LIMIT {
-            std::f32::MAX
-        } else {
-            (child.skills.count_levels() + 1) as f32 * EVOLVE_REQUIREMENT
-        };
+        child.set_skills(skills);
+        child.traits = self.traits[a].inherit(&self.traits[b]);
+        child.parents = Some((self.id[a], self.id[b]));
+        child.founder = self.founder[a];
+        child.generation = self.generation[a].max(self.generation[b]) + 1;
         child
     }
 }
 
+/// What a slime decided to do in the first phase of `SlimeController::update_step`.
+struct Decision {
+    position: Vec2,
+    speed: Vec2,
+    /// Energy after paying the movement cost.
+    energy: f32,
+    behavior: Behavior,
+    rest_until: f32,
+    /// Index of the slime it's courting.
+    breeding_target: Option<usize>,
+    /// Index and distance of the foods under the slime after moving.
+    foods: Vec<(usize, f32)>,
+}
+
+/// Claims an item for the slime `idx` if it's closer than the current claimer.
+fn claim(claimer: &mut Option<(usize, f32)>, idx: usize, distance: f32) {
+    if claimer.is_none_or(|(_, best)| distance < best) {
+        *claimer = Some((idx, distance));
+    }
+}
+
+/// Removes the claimed foods.
+fn remove_claimed(foods: &mut Vec<Food>, claimers: &[Option<(usize, f32)>]) {
+    let mut claimed = claimers.iter();
+    foods.retain(|_| claimed.next().unwrap().is_none());
+}
+
+/// Optional group behaviors.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GroupRules {
+    /// Wandering slimes steer following their flocking traits.
+    pub flocking: bool,
+    /// Scale of the flocking steering over the current direction.
+    pub flock_strength: f32,
+    /// Slimes share energy with starving kin close by.
+    pub kin_sharing: bool,
+    /// Slimes with less energy than this are starving.
+    pub starving_energy: f32,
+    /// Maximum energy given every step to a starving kin (scaled by generosity).
+    pub share_energy: f32,
+    /// Maximum distance between bodies to share energy.
+    pub share_distance: f32,
+}
+
+impl Default for GroupRules {
+    fn default() -> Self {
+        Self {
+            flocking: false,
+            flock_strength: 1.0,
+            kin_sharing: false,
+            starving_energy: 20.0,
+            share_energy: 1.0,
+            share_distance: 20.0,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct SlimeController {
     time: f32,
+    pub world_size: Vec2,
     pub config: SlimeConfig,
     pub last_time_cost: f32,
-    pub population: Vec<Slime>,
+    pub population: Population,
     /// How often (time steps) slimes consume 1 energy.
     pub time_cost_freq: f32,
     /// Time cooldown for slimes to breed.
     pub breeding_cooldown: f32,
     pub max_size_slow: f32,
+    /// Push apart overlapping slimes.
+    pub collisions: bool,
+    /// Step cost increase for each neighbour inside `crowding_radius` (0 to disable).
+    pub crowding_cost: f32,
+    /// Distance from the slime's border where other slimes count as neighbours.
+    pub crowding_radius: f32,
+    pub behavior_rules: BehaviorRules,
+    pub disease: DiseaseConfig,
+    pub group_rules: GroupRules,
+    /// Where slimes ate, died and were born, cleared by the simulation every step.
+    pub activity: Activity,
+    next_id: usize,
 }
 
 impl SlimeController {
     pub fn new(
+        world_size: Vec2,
         config: SlimeConfig,
         time_cost_freq: f32,
         breeding_cooldown: f32,
@@ -391,176 +833,640 @@ impl SlimeController {
     ) -> Self {
         Self {
             time: 0.0,
+            world_size,
             config,
             last_time_cost: 0.0,
-            population: Vec::new(),
+            population: Population::default(),
             time_cost_freq,
             breeding_cooldown,
             max_size_slow,
+            collisions: false,
+            crowding_cost: 0.0,
+            crowding_radius: 10.0,
+            behavior_rules: BehaviorRules::default(),
+            disease: DiseaseConfig::default(),
+            group_rules: GroupRules::default(),
+            activity: Activity::default(),
+            next_id: 0,
+        }
+    }
+
+    /// Removes every slime and starts ids and time over.
+    pub fn clear(&mut self) {
+        self.population.clear();
+        self.time = 0.0;
+        self.last_time_cost = 0.0;
+        self.next_id = 0;
+    }
+
+    /// Adds a slime to the population giving it a new id.
+    fn add(&mut self, mut slime: Slime) {
+        slime.id = self.next_id;
+        slime.born = self.time;
+        if slime.parents.is_none() {
+            slime.founder = slime.id;
+        }
+        self.next_id += 1;
+        self.population.push(slime);
+    }
+
+    /// Spawns one slime at a random position outside the obstacles.
+    pub fn spawn_one(&mut self, obstacles: &ObstacleMap) {
+        if let Some(slime) = Slime::spawn(&self.config, self.world_size, obstacles) {
+            self.add(slime);
         }
     }
 
-    pub fn spawn_one(&mut self) {
-        self.population.push(Slime::spawn(self.config.clone()));
+    pub fn spawn_n(&mut self, n: usize, obstacles: &ObstacleMap) {
+        (0..n).for_each(|_| self.spawn_one(obstacles))
+    }
+
+    /// Spawns a slime at `position` starting with the given skills.
+    pub fn spawn_at(&mut self, position: Vec2, skills: Skills) {
+        let mut slime = Slime::new(position, &self.config);
+        slime.set_skills(skills);
+        self.add(slime);
+    }
+
+    /// Spawns `n` slimes starting with the given skills.
+    pub fn spawn_with_skills(&mut self, n: usize, skills: Skills, obstacles: &ObstacleMap) {
+        for _ in 0..n {
+            if let Some(mut slime) = Slime::spawn(&self.config, self.world_size, obstacles) {
+                slime.set_skills(skills.clone());
+                self.add(slime);
+            }
+        }
     }
 
-    pub fn spawn_n(&mut self, n: usize) {
-        (0..n).for_each(|_| self.spawn_one())
+    /// Kills every slime touching the circle.
+    pub fn kill_in_radius(&mut self, center: Vec2, radius: f32) {
+        let deaths = &mut self.activity.deaths;
+        self.population.swap_remove_where(|p, i| {
+            let dead = p.is_point_inside(i, center, radius);
+            if dead {
+                deaths.push(p.position[i]);
+            }
+            dead
+        });
     }
 
-    /// Check timer for time cost.
+    /// Check timer for time cost, resting slimes pay a reduced cost. Dead slimes are
+    /// swap removed.
     pub fn check_time_cost(&mut self) {
         if (self.time - self.last_time_cost) >= self.time_cost_freq {
-            let mut i = 0;
-            while i < self.population.len() {
-                self.population[i].add_energy(-1.0);
-                if self.population[i].energy <= 0.0 {
-                    self.population.remove(i);
+            let population = &mut self.population;
+            for i in 0..population.len() {
+                let cost = if population.behavior[i] == Behavior::Rest {
+                    self.behavior_rules.rest_time_cost
                 } else {
-                    i += 1;
-                }
+                    1.0
+                };
+                population.add_energy(i, -cost);
             }
+            let deaths = &mut self.activity.deaths;
+            population.swap_remove_where(|p, i| {
+                let dead = p.energy[i] <= 0.0;
+                if dead {
+                    deaths.push(p.position[i]);
+                }
+                dead
+            });
             self.last_time_cost = self.time;
         }
     }
 
-    /// Check time cost, then, for each slime:
-    /// 1. Update slime position to get close its nearest food in vision range or nearest other slime if ready to breed.
-    /// 2. If on top a food, eat it.
-    /// 3. If possible try to breed.
-    /// 4. If didn't eat or breed, check if slime can jump.
-    /// 5. Check if it can evolve.
-    /// At the end of the loop childs (step 3) are added to population.
-    pub fn update_step(&mut self, foods: &mut Vec<Food>) {
-        self.check_time_cost();
-        self.reset_slime_states();
-        let n = self.population.len();
-        let mut childs = Vec::new();
-        for idx in 0..n {
-            // Step 1: Move
-            let mut slime = self.population[idx].clone();
-            let mut target_position_distance = None;
-            let breed_ready = slime.is_breed_ready(self.time, self.breeding_cooldown);
-            let mut breeding_target = None;
-
-            // - Get target position distance
-            if breed_ready {
-                if let Some((i, distance)) = slime.nearest_breeding_slime(
-                    idx,
-                    &self.population,
-                    self.time,
-                    self.breeding_cooldown,
-                ) {
-                    if (distance - slime.size) <= slime.vision_range() {
-                        target_position_distance = Some((self.population[i].position, distance));
-                        breeding_target = Some(i);
-                    }
-                }
+    /// Counts the slimes whose center is inside the crowding radius of the slime at `idx`.
+    fn count_neighbours(&self, idx: usize, grid: &SlimeGrid) -> usize {
+        let population = &self.population;
+        let position = population.position[idx];
+        let radius = population.size[idx] + self.crowding_radius;
+        grid.candidates(position, radius)
+            .filter(|&(i, other)| {
+                (i != idx) && (wrapped_diff(position, other, self.world_size).length() <= radius)
+            })
+            .count()
+    }
+
+    /// Checks the nearest other slime in line of sight in `mates` (the slimes able to
+    /// breed) at most `radius` away and returns its index and distance, ties go to the
+    /// lowest index.
+    fn nearest_breeding_slime(
+        &self,
+        idx: usize,
+        radius: f32,
+        obstacles: &ObstacleMap,
+        mates: &SlimeGrid,
+    ) -> Option<(usize, f32)> {
+        let position = self.population.position[idx];
+        mates
+            .candidates(position, radius)
+            .map(|(i, other)| (i, other, position.distance(other)))
+            .filter(|&(i, other, distance)| {
+                (i != idx) && (distance <= radius) && obstacles.is_visible(position, other)
+            })
+            .map(|(i, _, distance)| (i, distance))
+            .min_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)))
+    }
+
+    /// Returns the position of the nearest visible slime big enough to flee from.
+    fn nearest_threat(
+        &self,
+        idx: usize,
+        obstacles: &ObstacleMap,
+        grid: &SlimeGrid,
+    ) -> Option<Vec2> {
+        if self.behavior_rules.flee_size_ratio <= 0.0 {
+            return None;
+        }
+        let population = &self.population;
+        let (position, size) = (population.position[idx], population.size[idx]);
+        let min_size = size * self.behavior_rules.flee_size_ratio;
+        let vision_range = population.vision_range(idx, &self.config);
+        let positions = grid
+            .candidates(position, vision_range + size)
+            .filter(|&(i, other)| {
+                (i != idx)
+                    && (population.size[i] >= min_size)
+                    && obstacles.is_visible(position, other)
+            })
+            .map(|(_, other)| other)
+            .collect::<Vec<_>>();
+        nearest_position(position, positions.iter().copied())
+            .filter(|(_, distance)| (distance - size) <= vision_range)
+            .map(|(i, _)| positions[i])
+    }
+
+    /// Infects up to `n` random susceptible slimes, ignoring their resistance.
+    pub fn infect_random(&mut self, n: usize) {
+        for _ in 0..n {
+            let susceptible = (0..self.population.len())
+                .filter(|&i| self.population.health[i] == Health::Susceptible)
+                .collect::<Vec<_>>();
+            if susceptible.is_empty() {
+                break;
             }
-            if target_position_distance.is_none() {
-                if let Some((i, distance)) = slime.nearest_food(foods) {
-                    if (distance - slime.size) <= slime.vision_range() {
-                        target_position_distance = Some((foods[i].position, distance));
-                    }
+            let i = susceptible[gen_range(0, susceptible.len())];
+            self.population.health[i] = Health::Infected(self.time + self.disease.duration);
+        }
+    }
+
+    /// Infected slimes may infect every slime they are touching, across the world edges
+    /// too. Only the slimes infected before the step spread the disease.
+    fn spread_disease(&mut self) {
+        let world_size = self.world_size;
+        let grid = SlimeGrid::new(&self.population, world_size);
+        let population = &mut self.population;
+        let infected = (0..population.len())
+            .filter(|&i| population.health[i].is_infected())
+            .collect::<Vec<_>>();
+        for j in infected {
+            let (position, size) = (population.position[j], population.size[j]);
+            for (i, other) in grid.candidates(position, size + grid.max_size) {
+                let distance = wrapped_diff(position, other, world_size).length();
+                if (i != j) && (distance <= population.size[i] + size) {
+                    population.health[i] = self.disease.try_infect(
+                        population.health[i],
+                        population.traits[i].resistance,
+                        self.disease.contact_chance,
+                        self.time,
+                    );
                 }
             }
+        }
+    }
+
+    /// Boids steering for the slime at `idx` from the slimes in its vision range,
+    /// weighted by its flocking traits.
+    fn flock_steering(&self, idx: usize, grid: &SlimeGrid) -> Vec2 {
+        let population = &self.population;
+        let position = population.position[idx];
+        let range = population.size_vision(idx, &self.config);
+        let (mut count, mut center, mut velocity, mut separation) =
+            (0, Vec2::ZERO, Vec2::ZERO, Vec2::ZERO);
+        for (i, other) in grid.candidates(position, range) {
+            let diff = wrapped_diff(other, position, self.world_size);
+            let distance = diff.length();
+            if (i == idx) || (distance > range) {
+                continue;
+            }
+            count += 1;
+            // Closest copy of the other slime across the world edges
+            center += position - diff;
+            velocity += population.speed[i];
+            separation += diff / distance.max(1e-3).powi(2);
+        }
+        if count == 0 {
+            return Vec2::ZERO;
+        }
+        let center = center / count as f32;
+        let traits = &population.traits[idx];
+        (center - position).normalize_or_zero() * traits.cohesion
+            + velocity.normalize_or_zero() * traits.alignment
+            + separation.normalize_or_zero() * traits.separation
+    }
 
-            // - Update speed and move
-            if let Some((position, distance)) = target_position_distance {
-                let direction = get_angle_direction(slime.position, position);
-                slime.speed = polar_to_cartesian(
-                    slime.speed_factor(self.max_size_slow).min(distance),
-                    direction,
-                );
+    /// Starving slimes receive energy from a kin close by, as long as the donor keeps
+    /// at least the initial energy.
+    fn share_with_kin(&mut self) {
+        let rules = &self.group_rules;
+        let initial_energy = self.config.initial_energy;
+        let grid = SlimeGrid::new(&self.population, self.world_size);
+        let population = &mut self.population;
+        for i in 0..population.len() {
+            if population.energy[i] >= rules.starving_energy {
+                continue;
             }
-            slime.move_step();
-
-            // Step 2: Eat
-            let mut i = 0;
-            let mut did_eat = false;
-            while i < foods.len() {
-                if slime.is_point_inside(foods[i].position, 0.0) {
-                    slime.add_energy(foods[i].energy);
-                    foods.remove(i);
-                    did_eat = true;
-                } else {
-                    i += 1;
-                }
+            let radius = population.size[i] + grid.max_size + rules.share_distance;
+            let donor = grid
+                .candidates(population.position[i], radius)
+                .filter(|&(j, other)| {
+                    (j != i)
+                        && (population.energy[j] > initial_energy)
+                        && population.is_kin(i, j)
+                        && population.is_point_inside(
+                            i,
+                            other,
+                            population.size[j] + rules.share_distance,
+                        )
+                })
+                .map(|(j, _)| j)
+                .min();
+            if let Some(j) = donor {
+                let amount = (rules.share_energy * population.traits[j].generosity)
+                    .min(population.energy[j] - initial_energy);
+                population.add_energy(j, -amount);
+                population.add_energy(i, amount);
             }
+        }
+    }
 
-            // Step 3: Breed
-            if breed_ready {
-                if let Some(i) = breeding_target {
-                    let partner = &mut self.population[i];
-                    if slime.is_point_inside(partner.position, 0.0) {
-                        childs.push(slime.breed(partner, self.config.initial_energy, self.time));
-                    }
+    /// Pushes apart every pair of overlapping slimes, only checking the slimes in the
+    /// same or neighbouring cells of a `SlimeGrid`. Pushed slimes collide with the
+    /// obstacles like moving ones.
+    fn resolve_collisions(&mut self, obstacles: &ObstacleMap) {
+        let world_size = self.world_size;
+        let grid = SlimeGrid::new(&self.population, world_size);
+        let population = &mut self.population;
+        for i in 0..population.len() {
+            let radius = population.size[i] + grid.max_size;
+            // Positions in the grid are outdated once slimes are pushed
+            for (j, _) in grid.candidates(population.position[i], radius) {
+                if j <= i {
+                    continue;
+                }
+                if let Some((mut push_i, mut push_j)) = population.collision_push(i, j, world_size)
+                {
+                    let (size_i, size_j) = (population.size[i], population.size[j]);
+                    let positions = &mut population.position;
+                    let position_i = obstacles.resolve(positions[i], size_i, &mut push_i);
+                    let position_j = obstacles.resolve(positions[j], size_j, &mut push_j);
+                    positions[i] = wrap_around(&position_i, world_size);
+                    positions[j] = wrap_around(&position_j, world_size);
                 }
             }
+        }
+    }
 
-            // Step 4: Jump
-            if !did_eat && (slime.state != SlimeState::Breeding) && slime.is_jump_ready(self.time) {
-                if let Some((i, distance)) = slime.nearest_food(foods) {
-                    if (distance - slime.size) <= slime.jump_distance() {
-                        let nearest_food = &foods[i];
-                        slime.position = nearest_food.position;
-                        slime.add_energy(nearest_food.energy - JUMP_COST);
-                        foods.remove(i);
-                        slime.last_jump = self.time;
-                        slime.state = SlimeState::Jumping;
-                    }
+    /// Updates the biome and health of every slime, infected slimes lose energy.
+    fn update_conditions(&mut self, biomes: &BiomeMap) {
+        let (time, disease) = (self.time, &self.disease);
+        let population = &mut self.population;
+        population
+            .biome
+            .par_iter_mut()
+            .zip(population.position.par_iter())
+            .for_each(|(biome, &position)| *biome = biomes.modifiers(position));
+        population
+            .health
+            .par_iter_mut()
+            .zip(population.energy.par_iter_mut())
+            .zip(population.size.par_iter_mut())
+            .for_each(|((health, energy), size)| {
+                *health = disease.update_health(*health, time);
+                if disease.enabled && health.is_infected() {
+                    *energy -= disease.energy_cost;
+                    *size = energy_size(*energy);
                 }
+            });
+    }
+
+    /// Updates the behavior of the slime at `idx` and moves it accordingly:
+    /// - Court: get close to the nearest other slime in vision range if ready to breed.
+    /// - Seek: get close to the nearest food in vision range or follow the scent gradient.
+    /// - Flee: run away from a much bigger slime in vision range.
+    /// - Rest: stay still until the rest time is over.
+    /// - Wander: randomly turn a bit.
+    ///
+    /// Only reads the current population, so every slime can decide in parallel.
+    fn decide(
+        &self,
+        idx: usize,
+        foods: &FoodGrid,
+        slimes: &SlimeGrid,
+        mates: &SlimeGrid,
+        obstacles: &ObstacleMap,
+        scent: &ScentField,
+    ) -> Decision {
+        let population = &self.population;
+        let config = &self.config;
+        let mut position = population.position[idx];
+        let mut speed = population.speed[idx];
+        let mut behavior = population.behavior[idx];
+        let mut rest_until = population.rest_until[idx];
+        let size = population.size[idx];
+        let vision_range = population.vision_range(idx, config);
+        let mut target_position_distance = None;
+        let mut breeding_target = None;
+
+        // Get target position distance
+        if population.is_breed_ready(idx, self.time, self.breeding_cooldown, config) {
+            if let Some((i, distance)) =
+                self.nearest_breeding_slime(idx, vision_range + size, obstacles, mates)
+            {
+                target_position_distance = Some((population.position[i], distance));
+                breeding_target = Some(i);
             }
+        }
+        if target_position_distance.is_none() {
+            if let Some((i, distance)) = foods.nearest(position, vision_range + size, obstacles) {
+                target_position_distance = Some((foods.positions[i], distance));
+            }
+        }
 
-            // Step 5: Evolve
-            if slime.is_evolve_ready() {
-                slime.skills.add_skill(slime.skill_path);
-                if slime.skills.count_levels() >= EVOLVE_LIMIT {
-                    slime.next_skill_goal = std::f32::MAX;
-                } else {
-                    slime.next_skill_goal += EVOLVE_REQUIREMENT;
+        // Update behavior and speed
+        let speed_factor = population.speed_factor(idx, config, self.max_size_slow);
+        let scent_gradient = if scent.enabled {
+            Some(scent.gradient(position) * population.traits[idx].scent_sensitivity)
+                .filter(|g| g.length() >= SCENT_THRESHOLD)
+        } else {
+            None
+        };
+        if let Some((target, distance)) = target_position_distance {
+            behavior = if breeding_target.is_some() {
+                Behavior::Court
+            } else {
+                Behavior::Seek
+            };
+            let direction = get_angle_direction(position, target);
+            speed = polar_to_cartesian(speed_factor.min(distance), direction);
+        } else if let Some(threat) = self.nearest_threat(idx, obstacles, slimes) {
+            behavior = Behavior::Flee;
+            let direction = get_angle_direction(threat, position);
+            speed = polar_to_cartesian(speed_factor, direction);
+        } else if let Some(gradient) = scent_gradient {
+            behavior = Behavior::Seek;
+            let direction = get_angle_direction(Vec2::ZERO, gradient);
+            speed = polar_to_cartesian(speed_factor, direction);
+        } else if (behavior == Behavior::Rest) && (self.time < rest_until) {
+            // Keep resting
+        } else if gen_range(0.0, 1.0) < population.traits[idx].restfulness * REST_CHANCE {
+            behavior = Behavior::Rest;
+            rest_until = self.time + self.behavior_rules.rest_duration;
+        } else {
+            behavior = Behavior::Wander;
+            let mut heading = speed.normalize_or_zero();
+            if self.group_rules.flocking {
+                heading += self.flock_steering(idx, slimes) * self.group_rules.flock_strength;
+            }
+            let turn = self.behavior_rules.wander_turn;
+            let direction = get_angle_direction(Vec2::ZERO, heading) + gen_range(-turn, turn);
+            speed = polar_to_cartesian(speed_factor, direction);
+        }
+
+        // Move 1 step bouncing against obstacles, slimes below `FREE_MOVEMENT_TH` move
+        // without energy cost
+        let mut energy = population.energy[idx];
+        if behavior != Behavior::Rest {
+            let crowding = if self.crowding_cost > 0.0 {
+                1.0 + self.crowding_cost * self.count_neighbours(idx, slimes) as f32
+            } else {
+                1.0
+            };
+            position = obstacles.resolve(position, size, &mut speed);
+            position = wrap_around(&position, self.world_size);
+            if energy > FREE_MOVEMENT_TH {
+                let mult = (energy / 100.0).max(1.0);
+                energy -= population.step_cost(idx, config) * mult * crowding;
+            }
+        }
+
+        let size = energy_size(energy);
+        let foods = foods.within(position, size).collect();
+        Decision {
+            position,
+            speed,
+            energy,
+            behavior,
+            rest_until,
+            breeding_target,
+            foods,
+        }
+    }
+
+    /// Check time cost, then runs two phases:
+    ///
+    /// In the first one every slime decides what to do and moves (see `decide`) in
+    /// parallel, looking at the population from before the step (with the biome and
+    /// health of the slimes already updated). Each slime gets its
+    /// own random seed, so the result doesn't depend on the number of threads.
+    ///
+    /// The second one resolves the interactions in a fixed order:
+    /// 1. Each food under some slimes is eaten by the closest one.
+    /// 2. Slimes touching their courting target breed, closest pairs first. Each slime
+    ///    breeds at most once per step.
+    /// 3. Slimes that didn't eat or breed may jump to the nearest food in range, each food
+    ///    goes to the closest jumper.
+    /// 4. Slimes with enough energy evolve.
+    ///
+    /// Ties are broken by the lowest index. Then childs (step 2) are added to population.
+    ///
+    /// If the disease is enabled, infected slimes lose energy every step and may infect
+    /// the slimes they touch or breed with.
+    ///
+    /// If collisions are enabled, overlapping slimes are pushed apart at the end.
+    pub fn update_step(
+        &mut self,
+        foods: &mut Vec<Food>,
+        obstacles: &ObstacleMap,
+        biomes: &BiomeMap,
+        scent: &mut ScentField,
+    ) {
+        self.check_time_cost();
+        self.population.state.fill(SlimeState::Normal);
+        self.update_conditions(biomes);
+
+        // Phase 1: decide and move
+        let step_seed = rng::rand() as u64;
+        let rng_state = rng::state();
+        let grid = FoodGrid::new(foods, self.world_size);
+        let slimes = SlimeGrid::new(&self.population, self.world_size);
+        let mates = SlimeGrid::filtered(&self.population, self.world_size, |i| {
+            self.population
+                .is_breed_ready(i, self.time, self.breeding_cooldown, &self.config)
+        });
+        let decisions = (0..self.population.len())
+            .into_par_iter()
+            .map(|idx| {
+                let id = self.population.id[idx] as u64;
+                rng::srand(step_seed.wrapping_add(id.wrapping_mul(SEED_SPREAD)));
+                self.decide(idx, &grid, &slimes, &mates, obstacles, scent)
+            })
+            .collect::<Vec<_>>();
+        rng::set_state(rng_state);
+
+        // Phase 2: interactions
+        let (time, config) = (self.time, &self.config);
+        let population = &mut self.population;
+        let mut did_eat = vec![false; decisions.len()];
+        let mut eaten_by = vec![None; foods.len()];
+        for (idx, decision) in decisions.iter().enumerate() {
+            population.position[idx] = decision.position;
+            population.speed[idx] = decision.speed;
+            population.behavior[idx] = decision.behavior;
+            population.rest_until[idx] = decision.rest_until;
+            population.set_energy(idx, decision.energy);
+            for &(i, distance) in decision.foods.iter() {
+                claim(&mut eaten_by[i], idx, distance);
+            }
+        }
+        let mut breeding = decisions
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, d)| {
+                let target = d.breeding_target?;
+                let position = population.position[target];
+                population
+                    .is_point_inside(idx, position, 0.0)
+                    .then(|| (idx, target, population.position[idx].distance(position)))
+            })
+            .collect::<Vec<_>>();
+
+        // Step 1: Eat
+        for (food, eater) in foods.iter().zip(eaten_by.iter()) {
+            if let Some((idx, _)) = *eater {
+                population.add_energy(idx, food.energy);
+                did_eat[idx] = true;
+                self.activity.eaten.push(food.position);
+                scent.deposit(population.position[idx], scent.eat_deposit);
+            }
+        }
+        remove_claimed(foods, &eaten_by);
+
+        // Step 2: Breed
+        breeding.sort_by(|a, b| a.2.total_cmp(&b.2));
+        let mut childs = Vec::new();
+        for (idx, target, _) in breeding {
+            if !(population.is_breed_ready(idx, time, self.breeding_cooldown, config)
+                && population.is_breed_ready(target, time, self.breeding_cooldown, config))
+            {
+                continue;
+            }
+            let mut child = population.breed(idx, target, config.initial_energy, time, config);
+            let chance = self.disease.breed_chance;
+            if population.health[idx].is_infected() || population.health[target].is_infected() {
+                for i in [idx, target] {
+                    population.health[i] = self.disease.try_infect(
+                        population.health[i],
+                        population.traits[i].resistance,
+                        chance,
+                        time,
+                    );
                 }
+                child.health =
+                    self.disease
+                        .try_infect(child.health, child.traits.resistance, chance, time);
             }
+            childs.push(child);
+        }
 
-            self.population[idx] = slime;
+        // Step 3: Jump
+        let grid = FoodGrid::new(foods, self.world_size);
+        let jumps = (0..population.len())
+            .into_par_iter()
+            .filter(|&idx| {
+                !did_eat[idx]
+                    && (population.state[idx] != SlimeState::Breeding)
+                    && population.is_jump_ready(idx, time, config)
+            })
+            .filter_map(|idx| {
+                let range = population.size[idx] + population.jump_distance(idx, config);
+                grid.nearest(population.position[idx], range, obstacles)
+                    .map(|(i, distance)| (idx, i, distance))
+            })
+            .collect::<Vec<_>>();
+        let mut jumped_by = vec![None; foods.len()];
+        for (idx, i, distance) in jumps {
+            claim(&mut jumped_by[i], idx, distance);
+        }
+        for (food, jumper) in foods.iter().zip(jumped_by.iter()) {
+            if let Some((idx, _)) = *jumper {
+                population.position[idx] = food.position;
+                population.add_energy(idx, food.energy - JUMP_COST);
+                population.last_jump[idx] = time;
+                population.state[idx] = SlimeState::Jumping;
+                scent.deposit(food.position, scent.eat_deposit);
+                self.activity.eaten.push(food.position);
+            }
         }
+        remove_claimed(foods, &jumped_by);
+
+        // Step 4: Evolve
+        population
+            .skills
+            .par_iter_mut()
+            .zip(population.next_skill_goal.par_iter_mut())
+            .zip(population.energy.par_iter())
+            .zip(population.skill_path.par_iter())
+            .for_each(|(((skills, next_skill_goal), &energy), &skill_path)| {
+                if energy >= *next_skill_goal {
+                    skills.add_skill(skill_path);
+                    if skills.count_levels() >= EVOLVE_LIMIT {
+                        *next_skill_goal = f32::MAX;
+                    } else {
+                        *next_skill_goal += EVOLVE_REQUIREMENT;
+                    }
+                }
+            });
 
         // Add childs to population
-        self.population.append(&mut childs);
-    }
+        for child in childs {
+            self.activity.births.push(child.position);
+            self.add(child);
+        }
 
-    pub fn set_time(&mut self, time: f32) {
-        self.time = time;
-    }
+        if self.group_rules.kin_sharing {
+            self.share_with_kin();
+        }
 
-    fn reset_slime_states(&mut self) {
-        self.population
-            .iter_mut()
-            .for_each(|s| s.state = SlimeState::Normal);
+        if self.disease.enabled {
+            self.spread_disease();
+        }
+
+        if self.collisions {
+            self.resolve_collisions(obstacles);
+        }
     }
 
-    pub fn update_slime_configs(&mut self) {
-        self.population.iter_mut().for_each(|s| {
-            s.config = self.config.clone();
-        });
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::obstacle::Obstacle;
 
     impl Slime {
         pub fn create_test(position: Vec2) -> Self {
-            Self::new(position, SlimeConfig::default())
+            Self::new(position, &SlimeConfig::default())
         }
     }
 
     #[test]
     fn nearest_position_works() {
-        let slime = Slime::create_test(vec2(5.0, 5.0));
         let positions = [vec2(0.0, 0.0), vec2(2.0, 2.0), vec2(10.0, 10.0)];
-        let (i, distance) = slime.nearest_position(positions.into_iter()).unwrap();
+        let (i, distance) = nearest_position(vec2(5.0, 5.0), positions.into_iter()).unwrap();
         println!("distance={}", distance);
         assert_eq!(i, 1);
     }
@@ -583,22 +1489,266 @@ mod tests {
         }
     }
 
+    #[test]
+    fn collision_push_works() {
+        let mut population = Population::default();
+        population.push(Slime::create_test(vec2(0.0, 0.0)));
+        population.push(Slime::create_test(vec2(1.0, 0.0)));
+        population.add_energy(0, 200.0);
+        let world_size = vec2(100.0, 100.0);
+        let (push_a, push_b) = population.collision_push(0, 1, world_size).unwrap();
+        let a_position = population.position[0] + push_a;
+        let b_position = population.position[1] + push_b;
+        let total_size = population.size(0) + population.size(1);
+        assert!(a_position.distance(b_position) >= total_size - 1e-4);
+        assert!(push_a.length() < push_b.length());
+        population.position[1] = vec2(total_size + 1.0, 0.0);
+        assert!(population.collision_push(0, 1, world_size).is_none());
+        // Slimes overlap across the world edges
+        population.position[1] = vec2(99.0, 0.0);
+        let (push_a, push_b) = population.collision_push(0, 1, world_size).unwrap();
+        assert!(push_a.x > 0.0 && push_b.x < 0.0);
+    }
+
+    #[test]
+    fn food_grid_works() {
+        rng::srand(5);
+        let world_size = vec2(200.0, 100.0);
+        let mut foods = (0..300)
+            .map(|_| Food::create_test(random_position(world_size)))
+            .collect::<Vec<_>>();
+        // Food outside the world is found too
+        foods.push(Food::create_test(vec2(210.0, -5.0)));
+        let grid = FoodGrid::new(&foods, world_size);
+        let obstacles = ObstacleMap::default();
+        for _ in 0..50 {
+            let position = random_position(world_size);
+            let radius = gen_range(0.0, 80.0);
+            let mut found = grid.within(position, radius).collect::<Vec<_>>();
+            found.sort_by_key(|&(i, _)| i);
+            let expected = foods
+                .iter()
+                .enumerate()
+                .map(|(i, f)| (i, position.distance(f.position)))
+                .filter(|&(_, distance)| distance <= radius)
+                .collect::<Vec<_>>();
+            assert_eq!(found, expected);
+            let nearest = expected
+                .into_iter()
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            assert_eq!(grid.nearest(position, radius, &obstacles), nearest);
+        }
+        assert_eq!(
+            grid.nearest(vec2(205.0, 0.0), 10.0, &obstacles).unwrap().0,
+            300
+        );
+    }
+
+    #[test]
+    fn slime_grid_works() {
+        rng::srand(5);
+        let world_size = vec2(230.0, 100.0);
+        let mut population = Population::default();
+        for _ in 0..300 {
+            population.push(Slime::create_test(random_position(world_size)));
+        }
+        population.position[0] = world_size;
+        let grid = SlimeGrid::new(&population, world_size);
+        for _ in 0..50 {
+            let position = random_position(world_size);
+            let radius = gen_range(0.0, 150.0);
+            let mut found = grid
+                .candidates(position, radius)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+            found.sort_unstable();
+            let mut unique = found.clone();
+            unique.dedup();
+            assert_eq!(found, unique);
+            // Every slime within the radius across the world edges is a candidate
+            assert!((0..population.len())
+                .filter(|&i| {
+                    wrapped_diff(position, population.position[i], world_size).length() <= radius
+                })
+                .all(|i| found.binary_search(&i).is_ok()));
+        }
+    }
+
+    #[test]
+    fn spawn_avoids_obstacles() {
+        let mut obstacles = ObstacleMap::default();
+        obstacles.push(Obstacle::Rectangle {
+            position: vec2(0.0, 0.0),
+            size: vec2(80.0, 100.0),
+        });
+        let mut controller =
+            SlimeController::new(vec2(100.0, 100.0), SlimeConfig::default(), 1.0, 150.0, 0.5);
+        controller.spawn_n(50, &obstacles);
+        let population = &controller.population;
+        assert!(!population.is_empty());
+        assert!((0..population.len())
+            .all(|i| !obstacles.contains(population.position[i], population.size(i))));
+    }
+
+    #[test]
+    fn resolve_collisions_works() {
+        let mut controller =
+            SlimeController::new(vec2(100.0, 100.0), SlimeConfig::default(), 1.0, 150.0, 0.5);
+        controller.spawn_n(3, &ObstacleMap::default());
+        let positions = [vec2(50.0, 50.0), vec2(51.0, 50.0), vec2(99.5, 20.0)];
+        controller.population.position = positions.to_vec();
+        controller
+            .population
+            .push(Slime::create_test(vec2(0.5, 20.0)));
+        controller.resolve_collisions(&ObstacleMap::default());
+        let population = &controller.population;
+        for (a, b) in [(0, 1), (2, 3)] {
+            let diff = wrapped_diff(
+                population.position[a],
+                population.position[b],
+                vec2(100.0, 100.0),
+            );
+            assert!(diff.length() >= population.size(a) + population.size(b) - 1e-4);
+        }
+    }
+
+    #[test]
+    fn resolve_collisions_avoids_obstacles() {
+        let mut obstacles = ObstacleMap::default();
+        obstacles.push(Obstacle::Rectangle {
+            position: vec2(52.0, 0.0),
+            size: vec2(10.0, 100.0),
+        });
+        let mut controller =
+            SlimeController::new(vec2(100.0, 100.0), SlimeConfig::default(), 1.0, 150.0, 0.5);
+        controller.add(Slime::create_test(vec2(49.0, 50.0)));
+        controller.add(Slime::create_test(vec2(50.0, 50.0)));
+        controller.resolve_collisions(&obstacles);
+        let population = &controller.population;
+        assert!((0..population.len())
+            .all(|i| !obstacles.contains(population.position[i], population.size(i) - 1e-4)));
+    }
+
+    #[test]
+    fn neighbours_wrap_around() {
+        let world_size = vec2(200.0, 200.0);
+        let mut controller =
+            SlimeController::new(world_size, SlimeConfig::default(), 1.0, 150.0, 0.5);
+        controller.add(Slime::create_test(vec2(5.0, 100.0)));
+        controller.add(Slime::create_test(vec2(195.0, 100.0)));
+        controller.add(Slime::create_test(vec2(100.0, 100.0)));
+        let grid = SlimeGrid::new(&controller.population, world_size);
+        assert_eq!(controller.count_neighbours(0, &grid), 1);
+        assert_eq!(controller.count_neighbours(2, &grid), 0);
+        let traits = &mut controller.population.traits[0];
+        (traits.cohesion, traits.alignment, traits.separation) = (1.0, 0.0, 0.0);
+        assert_eq!(controller.flock_steering(0, &grid), vec2(-1.0, 0.0));
+    }
+
+    #[test]
+    fn spread_disease_wraps_around() {
+        let world_size = vec2(200.0, 200.0);
+        let mut controller =
+            SlimeController::new(world_size, SlimeConfig::default(), 1.0, 150.0, 0.5);
+        controller.disease.enabled = true;
+        controller.disease.contact_chance = 1.0;
+        controller.add(Slime::create_test(vec2(1.0, 100.0)));
+        controller.add(Slime::create_test(vec2(199.0, 100.0)));
+        controller.add(Slime::create_test(vec2(100.0, 100.0)));
+        controller
+            .population
+            .traits
+            .iter_mut()
+            .for_each(|t| t.resistance = 0.0);
+        controller.population.health[0] = Health::Infected(10.0);
+        controller.spread_disease();
+        assert!(controller.population.health[1].is_infected());
+        assert_eq!(controller.population.health[2], Health::Susceptible);
+    }
+
+    #[test]
+    fn resting_reduces_time_cost() {
+        let mut controller =
+            SlimeController::new(vec2(100.0, 100.0), SlimeConfig::default(), 1.0, 150.0, 0.5);
+        controller.behavior_rules.rest_time_cost = 0.5;
+        controller.add(Slime::create_test(vec2(0.0, 0.0)));
+        controller.add(Slime::create_test(vec2(0.0, 0.0)));
+        controller.population.behavior[1] = Behavior::Rest;
+        controller.set_time(1.0);
+        controller.check_time_cost();
+        let initial_energy = SlimeConfig::default().initial_energy;
+        assert_eq!(controller.population.energy(0), initial_energy - 1.0);
+        assert_eq!(controller.population.energy(1), initial_energy - 0.5);
+    }
+
+    #[test]
+    fn check_time_cost_removes_dead() {
+        let mut controller =
+            SlimeController::new(vec2(100.0, 100.0), SlimeConfig::default(), 1.0, 150.0, 0.5);
+        for _ in 0..4 {
+            controller.add(Slime::create_test(vec2(0.0, 0.0)));
+        }
+        controller.population.set_energy(0, 1.0);
+        controller.population.set_energy(2, 0.5);
+        controller.set_time(1.0);
+        controller.check_time_cost();
+        assert_eq!(controller.population.id, vec![3, 1]);
+    }
+
+    #[test]
+    fn share_with_kin_works() {
+        let mut controller =
+            SlimeController::new(vec2(100.0, 100.0), SlimeConfig::default(), 1.0, 150.0, 0.5);
+        controller.add(Slime::create_test(vec2(0.0, 0.0)));
+        controller.add(Slime::create_test(vec2(0.0, 0.0)));
+        let child = controller
+            .population
+            .breed(0, 1, 10.0, 0.0, &SlimeConfig::default());
+        controller.add(child);
+        controller.population.set_energy(2, 5.0);
+        controller.add(Slime::create_test(vec2(0.0, 0.0)));
+        controller
+            .population
+            .traits
+            .iter_mut()
+            .for_each(|t| t.generosity = 1.0);
+        controller.population.add_energy(0, 100.0);
+        controller.population.add_energy(3, 100.0);
+        assert_eq!(controller.population.children, vec![1, 1, 0, 0]);
+        assert_eq!(controller.population.founder, vec![0, 1, 0, 3]);
+        assert_eq!(controller.population.generation, vec![0, 0, 1, 0]);
+        assert!(controller.population.is_kin(2, 0));
+        assert!(!controller.population.is_kin(2, 3));
+        controller.share_with_kin();
+        assert_eq!(controller.population.energy(2), 6.0);
+        assert_eq!(controller.population.energy(3), 165.0);
+    }
+
     #[test]
     fn breed_works() {
-        let mut a = Slime::create_test(vec2(0.0, 0.0));
-        let mut b = Slime::create_test(vec2(0.0, 0.0));
-        let child = a.breed(&mut b, 10.0, 0.0);
+        let config = SlimeConfig::default();
+        let mut population = Population::default();
+        population.push(Slime::create_test(vec2(0.0, 0.0)));
+        population.push(Slime::create_test(vec2(0.0, 0.0)));
+        let child = population.breed(0, 1, 10.0, 0.0, &config);
         assert_eq!(child.skills.count_levels(), 0);
-        a.skills.vision = 6;
-        let child = a.breed(&mut b, 10.0, 0.0);
-        assert_eq!(child.skills.count_levels(), 2);
-        a.skills.vision = 6;
-        b.skills.jumper = 6;
-        let child = a.breed(&mut b, 10.0, 0.0);
-        assert_eq!(child.skills.count_levels(), 4);
-        a.skills = (6, 6, 6).into();
-        b.skills = (3, 3, 3).into();
-        let child = a.breed(&mut b, 10.0, 0.0);
-        assert_eq!(child.skills.count_levels(), 3);
+        // Each parent passes one skill with its level divided by 2..10, rounded up.
+        for _ in 0..20 {
+            population.skills[0] = (6, 0, 0).into();
+            population.skills[1] = (0, 0, 0).into();
+            let child = population.breed(0, 1, 10.0, 0.0, &config);
+            assert!((1..=3).contains(&child.skills.vision));
+            assert_eq!(child.skills.count_levels(), child.skills.vision);
+            population.skills[1] = (0, 0, 6).into();
+            let child = population.breed(0, 1, 10.0, 0.0, &config);
+            assert!((1..=3).contains(&child.skills.vision));
+            assert!((1..=3).contains(&child.skills.jumper));
+            assert_eq!(child.skills.efficiency, 0);
+            population.skills[0] = (6, 6, 6).into();
+            population.skills[1] = (3, 3, 3).into();
+            let child = population.breed(0, 1, 10.0, 0.0, &config);
+            assert!((2..=5).contains(&child.skills.count_levels()));
+            assert!(child.skills.unique_skills() <= 2);
+        }
     }
 }
@@ -0,0 +1,143 @@
+//! # Population statistics.
+//!
+//! Periodic snapshots of the world that can be exported as CSV.
+use crate::{disease::Health, food::Food, population::Population};
+use std::{fs, path::Path};
+
+const CSV_HEADER: &str = "time,slimes,food,vision,efficiency,jumper,infected,recovered,mean_energy";
+
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub time: f32,
+    pub slimes: usize,
+    pub food: usize,
+    /// Sum of the vision levels of all slimes.
+    pub vision: usize,
+    /// Sum of the efficiency levels of all slimes.
+    pub efficiency: usize,
+    /// Sum of the jumper levels of all slimes.
+    pub jumper: usize,
+    pub infected: usize,
+    pub recovered: usize,
+    /// Average energy of the slimes.
+    pub mean_energy: f32,
+}
+
+impl Stats {
+    pub fn collect(time: f32, slimes: &Population, foods: &[Food]) -> Self {
+        let mean_energy = if slimes.is_empty() {
+            0.0
+        } else {
+            slimes.energy.iter().sum::<f32>() / slimes.len() as f32
+        };
+        let stats = slimes.skills.iter().fold(
+            Self {
+                time,
+                slimes: slimes.len(),
+                food: foods.len(),
+                mean_energy,
+                ..Self::default()
+            },
+            |mut stats, skills| {
+                stats.vision += skills.vision;
+                stats.efficiency += skills.efficiency;
+                stats.jumper += skills.jumper;
+                stats
+            },
+        );
+        slimes.health.iter().fold(stats, |mut stats, health| {
+            stats.infected += health.is_infected() as usize;
+            stats.recovered += (*health == Health::Recovered) as usize;
+            stats
+        })
+    }
+
+    fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{}",
+            self.time,
+            self.slimes,
+            self.food,
+            self.vision,
+            self.efficiency,
+            self.jumper,
+            self.infected,
+            self.recovered,
+            self.mean_energy
+        )
+    }
+}
+
+pub struct StatsRecorder {
+    /// How often (time steps) stats are recorded.
+    pub frequency: f32,
+    pub records: Vec<Stats>,
+    last_record: Option<f32>,
+}
+
+impl StatsRecorder {
+    pub fn new(frequency: f32) -> Self {
+        Self {
+            frequency,
+            records: Vec::new(),
+            last_record: None,
+        }
+    }
+
+    /// Check timer to record the current stats.
+    pub fn check_record(&mut self, time: f32, slimes: &Population, foods: &[Food]) {
+        if self
+            .last_record
+            .is_none_or(|last| (time - last) >= self.frequency)
+        {
+            self.records.push(Stats::collect(time, slimes, foods));
+            self.last_record = Some(time);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.last_record = None;
+    }
+
+    /// Removes the records from `time` onwards.
+    pub fn truncate(&mut self, time: f32) {
+        self.records.retain(|r| r.time < time);
+        self.last_record = self.records.last().map(|r| r.time);
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(CSV_HEADER);
+        csv.push('\n');
+        for stats in self.records.iter() {
+            csv.push_str(&stats.csv_row());
+            csv.push('\n');
+        }
+        csv
+    }
+
+    pub fn export_csv(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        fs::write(path, self.to_csv()).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slime::Slime;
+    use macroquad::prelude::*;
+
+    #[test]
+    fn recorder_works() {
+        let mut recorder = StatsRecorder::new(10.0);
+        let mut slimes = Population::default();
+        slimes.push(Slime::create_test(vec2(0.0, 0.0)));
+        for time in 0..25 {
+            recorder.check_record(time as f32, &slimes, &[]);
+        }
+        assert_eq!(recorder.records.len(), 3);
+        let csv = recorder.to_csv();
+        assert_eq!(csv.lines().count(), 4);
+        assert_eq!(csv.lines().nth(2).unwrap(), "10,1,0,0,0,0,0,0,65");
+    }
+}
@@ -0,0 +1,299 @@
+//! # Parameter sweeps.
+//!
+//! Runs a base scenario headless many times, changing some of its fields over a grid
+//! of values or random ranges and for several seeds, using all the CPU cores.
+use crate::{
+    render::{FrameSettings, FrameWriter},
+    rng,
+    scenario::Scenario,
+    simulation::{Simulation, DEFAULT_WORLD_SIZE},
+    stats::Stats,
+};
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use toml::Value;
+
+/// Values for the field `field` of the scenario, either a grid of `values` or a
+/// `range` to draw random values from.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Param {
+    pub field: String,
+    #[serde(default)]
+    pub values: Vec<Value>,
+    #[serde(default)]
+    pub range: Option<(f64, f64)>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Sweep {
+    /// Base scenario file, relative to the sweep file. The default scenario is used
+    /// when missing.
+    #[serde(default)]
+    pub scenario: Option<PathBuf>,
+    /// Every combination runs once with each seed.
+    #[serde(default = "default_seeds")]
+    pub seeds: Vec<u64>,
+    /// Time steps after which a run stops if the slimes didn't go extinct.
+    pub max_steps: f32,
+    /// Random draws of the `range` parameters for each combination of the grid.
+    #[serde(default = "default_samples")]
+    pub samples: usize,
+    /// Seed used to draw the random values.
+    #[serde(default)]
+    pub sample_seed: u64,
+    #[serde(default)]
+    pub params: Vec<Param>,
+    /// Saves frames of every run, its directory is relative to the sweep file.
+    #[serde(default)]
+    pub frames: Option<FrameSettings>,
+}
+
+fn default_seeds() -> Vec<u64> {
+    vec![0]
+}
+
+fn default_samples() -> usize {
+    1
+}
+
+/// Value given to each field in one run.
+pub type Combination = Vec<(String, Value)>;
+
+pub struct RunResult {
+    pub combination: Combination,
+    pub seed: u64,
+    pub peak_population: usize,
+    pub stop_reason: String,
+    /// Stats at the end of the run, `stats.time` is the survival time.
+    pub stats: Stats,
+    /// Why the frames of the run couldn't be saved, the run goes on without them.
+    pub frames_error: Option<String>,
+}
+
+impl Sweep {
+    /// Loads a sweep from a TOML file, see `sweeps/example.toml`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut sweep: Self = toml::from_str(&content).map_err(|e| e.to_string())?;
+        if let Some(param) = sweep
+            .params
+            .iter()
+            .find(|p| p.values.is_empty() && p.range.is_none())
+        {
+            return Err(format!("Param {} has no values nor range", param.field));
+        }
+        if let (Some(scenario), Some(dir)) = (&sweep.scenario, path.parent()) {
+            sweep.scenario = Some(dir.join(scenario));
+        }
+        if let (Some(frames), Some(dir)) = (&mut sweep.frames, path.parent()) {
+            frames.dir = dir.join(&frames.dir);
+        }
+        Ok(sweep)
+    }
+
+    pub fn base_scenario(&self) -> Result<Scenario, String> {
+        match &self.scenario {
+            Some(path) => Scenario::load(path),
+            None => Ok(Scenario::default()),
+        }
+    }
+
+    /// Lists the combinations of the grid values, each one repeated `samples` times
+    /// with new random values when there are range params.
+    pub fn combinations(&self) -> Vec<Combination> {
+        let mut combinations = vec![Combination::new()];
+        for param in self.params.iter().filter(|p| p.range.is_none()) {
+            combinations = combinations
+                .iter()
+                .flat_map(|c| {
+                    param.values.iter().map(move |v| {
+                        let mut c = c.clone();
+                        c.push((param.field.clone(), v.clone()));
+                        c
+                    })
+                })
+                .collect();
+        }
+        let ranges = self
+            .params
+            .iter()
+            .filter_map(|p| p.range.map(|r| (&p.field, r)))
+            .collect::<Vec<_>>();
+        if ranges.is_empty() {
+            return combinations;
+        }
+        let state = rng::state();
+        rng::srand(self.sample_seed);
+        let combinations = combinations
+            .iter()
+            .flat_map(|c| (0..self.samples).map(move |_| c.clone()))
+            .map(|mut c| {
+                for &(field, (min, max)) in ranges.iter() {
+                    c.push((field.clone(), Value::Float(rng::gen_range(min, max))));
+                }
+                c
+            })
+            .collect();
+        rng::set_state(state);
+        combinations
+    }
+
+    /// Runs every combination with every seed in parallel.
+    pub fn run(&self) -> Result<Vec<RunResult>, String> {
+        let base = self.base_scenario()?;
+        let runs = self
+            .combinations()
+            .into_iter()
+            .flat_map(|c| self.seeds.iter().map(move |&seed| (c.clone(), seed)))
+            .map(|(combination, seed)| {
+                let mut scenario = base.with_fields(&combination)?;
+                scenario.seed = Some(seed);
+                scenario.stop.max_steps = Some(self.max_steps);
+                scenario.stop.extinction = true;
+                Ok((combination, seed, scenario))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        runs.into_par_iter()
+            .enumerate()
+            .map(|(i, (combination, seed, scenario))| {
+                let mut simulation = Simulation::new(scenario, DEFAULT_WORLD_SIZE)?;
+                let mut frames_error = None;
+                let mut frames = self.frames.as_ref().and_then(|settings| {
+                    let dir = settings.dir.join(format!("run_{:04}_seed_{}", i, seed));
+                    FrameWriter::new(settings, dir)
+                        .map_err(|e| frames_error = Some(e))
+                        .ok()
+                });
+                let mut peak_population = simulation.slime_controller.population.len();
+                while simulation.stopped.is_none() {
+                    if let Some(Err(e)) = frames.as_mut().map(|f| f.update(&simulation)) {
+                        frames_error = Some(e);
+                        frames = None;
+                    }
+                    simulation.step();
+                    peak_population =
+                        peak_population.max(simulation.slime_controller.population.len());
+                }
+                if let Some(Err(e)) = frames.as_mut().map(|f| f.finish(&simulation)) {
+                    frames_error = Some(e);
+                }
+                Ok(RunResult {
+                    combination,
+                    seed,
+                    peak_population,
+                    stop_reason: simulation.stopped.take().unwrap_or_default(),
+                    stats: simulation.stats(),
+                    frames_error,
+                })
+            })
+            .collect()
+    }
+
+    /// Writes one row per run: the param values, the seed and the run results.
+    pub fn to_csv(&self, results: &[RunResult]) -> String {
+        let mut csv = self
+            .params
+            .iter()
+            .map(|p| format!("{},", p.field))
+            .collect::<String>();
+        csv.push_str("seed,survival_time,peak_population,stop_reason,");
+        csv.push_str("slimes,food,vision,efficiency,jumper,infected,recovered\n");
+        for result in results {
+            let mut values = self
+                .params
+                .iter()
+                .map(|p| {
+                    result
+                        .combination
+                        .iter()
+                        .find(|(field, _)| *field == p.field)
+                        .map(|(_, v)| v.to_string())
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<_>>();
+            let stats = &result.stats;
+            values.extend([
+                result.seed.to_string(),
+                stats.time.to_string(),
+                result.peak_population.to_string(),
+                result.stop_reason.clone(),
+                stats.slimes.to_string(),
+                stats.food.to_string(),
+                stats.vision.to_string(),
+                stats.efficiency.to_string(),
+                stats.jumper.to_string(),
+                stats.infected.to_string(),
+                stats.recovered.to_string(),
+            ]);
+            csv.push_str(&values.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep_works() {
+        let sweep: Sweep = toml::from_str(
+            r#"
+            seeds = [1, 2]
+            max_steps = 50.0
+            samples = 2
+
+            [[params]]
+            field = "slime.vision_skill"
+            values = [1.0, 2.0, 3.0]
+
+            [[params]]
+            field = "slimes.breeding_cooldown"
+            range = [50.0, 300.0]
+            "#,
+        )
+        .unwrap();
+        let combinations = sweep.combinations();
+        assert_eq!(combinations.len(), 6);
+        rng::srand(42);
+        let state = rng::state();
+        sweep.combinations();
+        assert_eq!(rng::state(), state, "The caller's generator is kept");
+        assert_eq!(
+            combinations,
+            sweep.combinations(),
+            "Sampling is reproducible"
+        );
+        let results = sweep.run().unwrap();
+        assert_eq!(results.len(), 12);
+        assert!(results.iter().all(|r| r.stats.time <= 50.0));
+        let csv = sweep.to_csv(&results);
+        assert_eq!(csv.lines().count(), 13);
+        assert!(csv.starts_with("slime.vision_skill,slimes.breeding_cooldown,seed,"));
+    }
+
+    #[test]
+    fn frames_error_keeps_running() {
+        // Frames can't be saved under a file
+        let file = std::env::temp_dir().join("evolution_simulation_not_a_dir");
+        fs::write(&file, "").unwrap();
+        let mut sweep: Sweep = toml::from_str("seeds = [1, 2]\nmax_steps = 20.0").unwrap();
+        sweep.frames = Some(FrameSettings {
+            dir: file.clone(),
+            every: 10.0,
+            scale: 0.1,
+            color_mode: Default::default(),
+        });
+        let results = sweep.run().unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.frames_error.is_some()));
+        assert!(results.iter().all(|r| r.stats.time == 20.0));
+        fs::remove_file(&file).unwrap();
+    }
+}
@@ -0,0 +1,102 @@
+//! # Movement trails.
+//!
+//! Last positions of each slime, drawn as a line that fades with age.
+use crate::population::Population;
+use macroquad::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+pub struct Trails {
+    /// Positions kept for each slime.
+    pub length: usize,
+    /// Trail of each slime id, the newest position at the back.
+    trails: HashMap<usize, VecDeque<Vec2>>,
+    last_time: Option<f32>,
+}
+
+impl Trails {
+    pub fn new(length: usize) -> Self {
+        Self {
+            length,
+            trails: HashMap::new(),
+            last_time: None,
+        }
+    }
+
+    /// Adds the current positions if the simulation advanced since the last call, the
+    /// trails of dead slimes are dropped.
+    pub fn record(&mut self, time: f32, population: &Population) {
+        match self.last_time {
+            Some(last) if last == time => return,
+            // The simulation went back in time
+            Some(last) if time < last => self.trails.clear(),
+            _ => {}
+        }
+        self.last_time = Some(time);
+        let alive = population.id.iter().collect::<HashSet<_>>();
+        self.trails.retain(|id, _| alive.contains(id));
+        for (&id, &position) in population.id.iter().zip(population.position.iter()) {
+            let trail = self.trails.entry(id).or_default();
+            trail.push_back(position);
+            while trail.len() > self.length {
+                trail.pop_front();
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.trails.clear();
+        self.last_time = None;
+    }
+
+    /// Draws the trail of each slime with its skill path color. Segments longer than
+    /// half the world (wrapping around the edges) are skipped.
+    pub fn draw(&self, population: &Population, world_size: Vec2, thickness: f32) {
+        let max_segment = world_size.min_element() / 2.0;
+        for (id, skill_path) in population.id.iter().zip(population.skill_path.iter()) {
+            let trail = match self.trails.get(id) {
+                Some(trail) => trail,
+                None => continue,
+            };
+            let color = skill_path.color();
+            let n = trail.len() as f32;
+            for (i, (a, b)) in trail.iter().zip(trail.iter().skip(1)).enumerate() {
+                if a.distance(*b) > max_segment {
+                    continue;
+                }
+                let alpha = (i + 1) as f32 / n * 0.8;
+                let color = Color::new(color.r, color.g, color.b, alpha);
+                draw_line(a.x, a.y, b.x, b.y, thickness, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slime::{Slime, SlimeConfig};
+
+    #[test]
+    fn record_works() {
+        let mut population = Population::default();
+        let mut slime = Slime::new(vec2(0.0, 0.0), &SlimeConfig::default());
+        for id in 0..2 {
+            slime.id = id;
+            population.push(slime.clone());
+        }
+        let mut trails = Trails::new(3);
+        for time in 0..5 {
+            population.position[0].x = time as f32;
+            trails.record(time as f32, &population);
+            // Frames without steps don't add positions
+            trails.record(time as f32, &population);
+        }
+        assert_eq!(
+            trails.trails[&0],
+            [2.0, 3.0, 4.0].map(|x| vec2(x, 0.0)).to_vec()
+        );
+        population.swap_remove(1);
+        trails.record(5.0, &population);
+        assert_eq!(trails.trails.len(), 1);
+    }
+}
@@ -1,32 +1,84 @@
-use macroquad::{prelude::*, rand::gen_range};
+use crate::rng::gen_range;
+use macroquad::prelude::*;
 
-pub fn random_screen_position() -> Vec2 {
-    vec2(
-        gen_range(0.0, screen_width()),
-        gen_range(0.0, screen_height()),
-    )
+pub fn random_position(world_size: Vec2) -> Vec2 {
+    vec2(gen_range(0.0, world_size.x), gen_range(0.0, world_size.y))
 }
 
-/// Wraps a positions offsets around the screen
-pub fn wrap_around(pos: &Vec2) -> Vec2 {
+/// Wraps a positions offsets around the world
+pub fn wrap_around(pos: &Vec2, world_size: Vec2) -> Vec2 {
     let mut new_pos = Vec2::new(pos.x, pos.y);
-    if new_pos.x > screen_width() {
+    if new_pos.x > world_size.x {
         new_pos.x = 0.;
     }
     if new_pos.x < 0. {
-        new_pos.x = screen_width()
+        new_pos.x = world_size.x
     }
-    if new_pos.y > screen_height() {
+    if new_pos.y > world_size.y {
         new_pos.y = 0.;
     }
     if new_pos.y < 0. {
-        new_pos.y = screen_height()
+        new_pos.y = world_size.y
     }
     new_pos
 }
 
+/// Shortest offset from `a` to `b` in a world that wraps around its edges.
+pub fn wrapped_diff(a: Vec2, b: Vec2, world_size: Vec2) -> Vec2 {
+    let diff = b - a;
+    diff - (diff / world_size).round() * world_size
+}
+
 /// Get angle direction from point a to b
 pub fn get_angle_direction(a: Vec2, b: Vec2) -> f32 {
     let diff = b - a;
     diff.y.atan2(diff.x)
 }
+
+/// Serializes `Vec2` fields as `[x, y]`, for `#[serde(with = "crate::utils::serde_vec2")]`.
+pub mod serde_vec2 {
+    use macroquad::prelude::Vec2;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Vec2, serializer: S) -> Result<S::Ok, S::Error> {
+        [value.x, value.y].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec2, D::Error> {
+        <[f32; 2]>::deserialize(deserializer).map(Vec2::from)
+    }
+
+    /// Same for `Option<Vec2>` fields.
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            value: &Option<Vec2>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            value.map(|v| [v.x, v.y]).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Vec2>, D::Error> {
+            Ok(Option::<[f32; 2]>::deserialize(deserializer)?.map(Vec2::from))
+        }
+    }
+
+    /// Same for `Vec<Vec2>` fields.
+    pub mod vec {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(value: &[Vec2], serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(value.iter().map(|v| [v.x, v.y]))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<Vec2>, D::Error> {
+            let points = Vec::<[f32; 2]>::deserialize(deserializer)?;
+            Ok(points.into_iter().map(Vec2::from).collect())
+        }
+    }
+}
@@ -1,6 +1,21 @@
 use crate::{
-    food::{FoodController, FOOD_SIZE},
-    slime::{SlimeConfig, SlimeController, SlimeState},
+    biome::{Biome, BiomeMap},
+    camera::WorldCamera,
+    charts::{count_scatter_chart, histogram_chart, line_chart, stacked_area_chart},
+    clock::Clock,
+    coloring::{ColorMode, Palette, INFECTED_COLOR},
+    distribution::{Distributions, Histogram},
+    event::{Event, EventSchedule},
+    food::FOOD_SIZE,
+    heatmap::HeatmapKind,
+    history::History,
+    obstacle::{Obstacle, ObstacleMap},
+    replay::{Action, Player, Recorder, Replay},
+    rng,
+    scenario::{Scenario, SCENARIOS_DIR},
+    simulation::{Simulation, DEFAULT_WORLD_SIZE},
+    stats::Stats,
+    trail::Trails,
 };
 use human_format::Formatter;
 use macroquad::{
@@ -8,33 +23,248 @@ use macroquad::{
     prelude::*,
     ui::{root_ui, widgets, Skin},
 };
-use std::ops::Div;
+use std::{
+    ops::Div,
+    path::{Path, PathBuf},
+};
+
+const OBSTACLE_TOOLS: [&str; 4] = ["None", "Rectangle", "Circle", "Wall"];
+const OBSTACLE_COLOR: Color = DARKGRAY;
+/// File where stats are exported.
+const STATS_PATH: &str = "stats.csv";
+/// File where the current settings are saved as a scenario.
+const SAVED_SCENARIO_PATH: &str = "scenarios/saved.toml";
+/// Files where heatmaps are exported, `{}` is replaced by their name.
+const HEATMAP_PATH: &str = "heatmap_{}.png";
+/// File where recorded runs are saved and played from.
+const REPLAY_PATH: &str = "replay.toml";
+/// Time steps between rewind snapshots.
+const SNAPSHOT_INTERVAL: f32 = 200.0;
+/// Maximum number of rewind snapshots.
+const SNAPSHOT_CAPACITY: usize = 50;
+/// Zoom factor of each mouse wheel step.
+const ZOOM_STEP: f32 = 1.2;
+/// Maximum mouse movement (pixels) between press and release to count as a click.
+const CLICK_DISTANCE: f32 = 4.0;
+const SELECTED_COLOR: Color = WHITE;
+const CHART_SIZE: Vec2 = glam::const_vec2!([290.0, 60.0]);
+const HISTOGRAM_SIZE: Vec2 = glam::const_vec2!([290.0, 40.0]);
+const JUMP_RANGE_COLOR: Color = Color::new(0.0, 0.89, 0.19, 0.5);
 
 pub struct World {
-    food_controller: FoodController,
-    slime_controller: SlimeController,
-    simulation_speed: f32,
-    time: f32,
+    simulation: Simulation,
+    /// Files listed in the scenario picker.
+    scenario_files: Vec<PathBuf>,
+    /// Selected index from `scenario_files`.
+    scenario_index: usize,
+    /// Parameters of the events triggered from the UI.
+    ui_events: [Event; 5],
+    clock: Clock,
+    camera: WorldCamera,
+    /// Snapshots to rewind the simulation.
+    history: History,
+    /// Records the UI interventions while set.
+    recorder: Option<Recorder>,
+    /// Replay being played back.
+    player: Option<Player>,
+    /// Steps run by "Step N".
+    step_n: f32,
+    /// Steps gone back by "Rewind".
+    rewind_steps: f32,
     settings_open: bool,
-    initial_food: f32,
-    initial_slimes: f32,
+    charts_open: bool,
+    /// Time steps shown in the charts.
+    charts_window: f32,
+    histograms_open: bool,
+    /// Time steps between updates of the histograms.
+    histograms_steps: f32,
+    distributions: Option<Distributions>,
+    /// Heatmaps drawn for each `HeatmapKind`.
+    heatmaps_visible: [bool; 4],
+    /// Count the heatmaps even when none is drawn, to export them.
+    record_heatmaps: bool,
+    trails: Trails,
+    show_trails: bool,
+    /// Draw the vision range of every slime, not only the hovered one.
+    show_vision: bool,
+    show_jump_range: bool,
+    /// Selected index from `ColorMode::ALL`.
+    color_mode: usize,
+    /// Selected index from `OBSTACLE_TOOLS`.
+    obstacle_tool: usize,
+    /// Points of the obstacle being drawn.
+    obstacle_points: Vec<Vec2>,
+    /// Screen position where the left button was pressed.
+    click_start: Option<Vec2>,
+    /// Id of the slime shown in the inspector.
+    selected: Option<usize>,
+    /// Keep the camera centered on the selected slime.
+    follow: bool,
+    /// When the followed slime dies, follow its largest living child.
+    follow_children: bool,
 }
 
 impl World {
     pub fn new(initial_food: usize, initial_slimes: usize) -> Self {
-        let food_controller = FoodController::new(15.0, 300.0, (30.0, 90.0), (0.85, 2.2));
-        let slime_controller = SlimeController::new(SlimeConfig::default(), 8.0, 150.0, 0.5);
-        let mut world = Self {
-            food_controller,
-            slime_controller,
-            simulation_speed: 1.0,
-            time: 0.0,
+        let scenario = Scenario {
+            initial_food,
+            initial_slimes,
+            ..Scenario::default()
+        };
+        let simulation = Simulation::new(scenario, DEFAULT_WORLD_SIZE)
+            .expect("The default scenario has no files to load");
+        let mut camera = WorldCamera::default();
+        camera.fit(simulation.world_size, screen_size());
+        Self {
+            simulation,
+            scenario_files: Scenario::list(SCENARIOS_DIR),
+            scenario_index: 0,
+            ui_events: [
+                Event::Famine { duration: 2000.0 },
+                Event::Meteor {
+                    position: None,
+                    radius: 80.0,
+                },
+                Event::Bloom { amount: 50 },
+                Event::Plague { infected: 3 },
+                Event::Migration {
+                    amount: 5,
+                    skills: (0, 0, 0),
+                },
+            ],
+            clock: Clock::default(),
+            camera,
+            history: History::new(SNAPSHOT_INTERVAL, SNAPSHOT_CAPACITY),
+            recorder: None,
+            player: None,
+            step_n: 100.0,
+            rewind_steps: 1000.0,
             settings_open: false,
-            initial_food: initial_food as f32,
-            initial_slimes: initial_slimes as f32,
+            charts_open: false,
+            charts_window: 20000.0,
+            histograms_open: false,
+            histograms_steps: 100.0,
+            distributions: None,
+            heatmaps_visible: [false; 4],
+            record_heatmaps: false,
+            trails: Trails::new(30),
+            show_trails: false,
+            show_vision: false,
+            show_jump_range: false,
+            color_mode: 0,
+            obstacle_tool: 0,
+            obstacle_points: Vec::new(),
+            click_start: None,
+            selected: None,
+            follow: false,
+            follow_children: true,
+        }
+    }
+
+    /// Loads a scenario replacing the whole world and resets the simulation. Stops
+    /// recording and playing replays.
+    pub fn load_scenario(&mut self, scenario: Scenario) -> Result<(), String> {
+        let heatmaps_window = self.simulation.heatmaps.window;
+        self.simulation = Simulation::new(scenario, DEFAULT_WORLD_SIZE)?;
+        self.simulation.heatmaps.window = heatmaps_window;
+        self.camera.fit(self.simulation.world_size, screen_size());
+        self.history.clear();
+        self.trails.clear();
+        self.selected = None;
+        self.follow = false;
+        self.recorder = None;
+        self.player = None;
+        Ok(())
+    }
+
+    /// Plays the replay at `path` from its start.
+    pub fn play_replay(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        let replay = Replay::load(path)?;
+        self.load_scenario(replay.scenario.clone())?;
+        self.player = Some(Player::new(replay));
+        Ok(())
+    }
+
+    /// Restarts the simulation from the current settings (with a seed) and records the
+    /// interventions made from then on.
+    fn start_recording(&mut self) -> Result<(), String> {
+        let mut scenario = self.simulation.scenario();
+        scenario.seed.get_or_insert_with(|| rng::rand() as u64);
+        self.load_scenario(scenario)?;
+        self.recorder = Some(Recorder::new(&self.simulation)?);
+        Ok(())
+    }
+
+    /// Stops recording and saves the replay to `REPLAY_PATH`.
+    fn stop_recording(&mut self) {
+        if let Some(mut recorder) = self.recorder.take() {
+            recorder.check_settings(&self.simulation);
+            if let Err(e) = recorder.replay.save(REPLAY_PATH) {
+                eprintln!("Failed to save replay to {}: {}", REPLAY_PATH, e);
+            }
+        }
+    }
+
+    /// Applies an intervention from the UI, recording it if needed. Taking over a
+    /// replay stops it.
+    fn act(&mut self, action: Action) {
+        self.player = None;
+        if action == Action::Reset {
+            self.history.clear();
+        }
+        let result = match &mut self.recorder {
+            Some(recorder) => recorder.apply(&mut self.simulation, action),
+            None => self.simulation.apply_action(&action),
         };
-        world.reset();
-        world
+        if let Err(e) = result {
+            eprintln!("Failed to apply action: {}", e);
+        }
+    }
+
+    /// Records the settings changed from the UI. Changing them during a replay stops it,
+    /// as the run would no longer match the recording.
+    fn record_settings(&mut self) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.check_settings(&self.simulation);
+        }
+        if let Some(player) = &self.player {
+            if player.settings_changed(&self.simulation) {
+                eprintln!("Settings changed during the replay, stopped playing it");
+                self.player = None;
+            }
+        }
+    }
+
+    /// Remembers the settings left by the replay steps, see `record_settings`.
+    fn sync_player(&mut self) {
+        if let Some(player) = &mut self.player {
+            player.sync(&self.simulation);
+        }
+    }
+
+    pub fn world_size(&self) -> Vec2 {
+        self.simulation.world_size
+    }
+
+    /// Replaces the world biomes and resets the simulation.
+    pub fn with_biomes(mut self, biomes: BiomeMap) -> Self {
+        self.simulation.biomes = biomes;
+        self.simulation.reset();
+        self
+    }
+
+    /// Replaces the world event schedule and resets the simulation.
+    pub fn with_events(mut self, events: EventSchedule) -> Self {
+        self.simulation.events = events;
+        self.simulation.reset();
+        self
+    }
+
+    /// Replaces the world obstacles and resets the simulation.
+    pub fn with_obstacles(mut self, obstacles: ObstacleMap) -> Self {
+        self.simulation.obstacles = obstacles;
+        self.simulation.reset();
+        self
     }
 
     pub async fn run(mut self) {
@@ -43,93 +273,500 @@ impl World {
             clear_background(BLACK);
 
             // Updates
-            for _ in 0..(self.simulation_speed.round() as usize) {
-                self.food_controller.set_time(self.time);
-                self.slime_controller.set_time(self.time);
-                self.food_controller.update_step();
-                self.slime_controller
-                    .update_step(&mut self.food_controller.population);
-                self.time += 1.0;
+            self.simulation.heatmaps.enabled =
+                self.record_heatmaps || self.heatmaps_visible.contains(&true);
+            self.handle_keys();
+            self.handle_camera();
+            self.handle_selection();
+            let (simulation, history, player) =
+                (&mut self.simulation, &mut self.history, &mut self.player);
+            self.clock
+                .tick(get_time, || Self::advance(simulation, history, player));
+            self.sync_player();
+            self.update_follow();
+            self.update_distributions();
+            if self.show_trails {
+                self.trails.record(
+                    self.simulation.time,
+                    &self.simulation.slime_controller.population,
+                );
+            } else {
+                self.trails.clear();
             }
 
             // Draws
-            self.draw_food();
-            self.draw_slimes();
+            self.draw_world();
+            set_default_camera();
+            self.draw_hovered();
             self.draw_status();
+            self.draw_legend();
             self.draw_ui();
+            self.edit_obstacles();
+            self.record_settings();
             next_frame().await;
         }
     }
 
+    /// Runs one step applying the replay actions due, returns false if the simulation
+    /// is stopped.
+    fn advance(
+        simulation: &mut Simulation,
+        history: &mut History,
+        player: &mut Option<Player>,
+    ) -> bool {
+        if simulation.stopped.is_some() {
+            return false;
+        }
+        if let Some(replay) = player {
+            if let Err(e) = replay.apply(simulation) {
+                eprintln!("Failed to play replay: {}", e);
+                *player = None;
+            } else if replay.is_finished() {
+                *player = None;
+            }
+        }
+        history.check_snapshot(simulation);
+        simulation.step();
+        true
+    }
+
+    /// Runs `n` steps right away, even if paused.
+    fn step(&mut self, n: usize) {
+        self.record_settings();
+        for _ in 0..n {
+            if !Self::advance(&mut self.simulation, &mut self.history, &mut self.player) {
+                break;
+            }
+        }
+        self.sync_player();
+    }
+
+    /// Rewinding is disabled while recording or playing replays, as the recorded
+    /// actions can't follow it.
+    fn rewind(&mut self) {
+        if self.recorder.is_some() || self.player.is_some() {
+            return;
+        }
+        self.history
+            .rewind(&mut self.simulation, self.rewind_steps.round());
+    }
+
+    /// Keyboard shortcuts:
+    /// - Space: pause / resume.
+    /// - Right: step one (with shift: step N).
+    /// - Left: rewind.
+    /// - F: fit the world in the window.
+    fn handle_keys(&mut self) {
+        if is_key_pressed(KeyCode::Space) {
+            self.clock.paused = !self.clock.paused;
+        }
+        if is_key_pressed(KeyCode::Right) {
+            let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+            self.step(if shift {
+                self.step_n.round() as usize
+            } else {
+                1
+            });
+        }
+        if is_key_pressed(KeyCode::Left) {
+            self.rewind();
+        }
+        if is_key_pressed(KeyCode::F) {
+            self.camera.fit(self.simulation.world_size, screen_size());
+        }
+    }
+
+    /// Zooms with the mouse wheel and pans dragging with the middle button, or with the
+    /// left one when no obstacle tool is selected. Dragging the mouse further than a
+    /// click stops following.
+    fn handle_camera(&mut self) {
+        let mouse = mouse_vec();
+        let over_ui = root_ui().is_mouse_over(mouse);
+        let (_, wheel) = mouse_wheel();
+        if wheel != 0.0 && !over_ui {
+            self.camera
+                .zoom_at(mouse, ZOOM_STEP.powf(wheel.signum()), screen_size());
+        }
+        let left_pans = OBSTACLE_TOOLS[self.obstacle_tool] == "None";
+        if !over_ui
+            && (is_mouse_button_pressed(MouseButton::Middle)
+                || (left_pans && is_mouse_button_pressed(MouseButton::Left)))
+        {
+            self.camera.start_drag(mouse);
+        }
+        if is_mouse_button_down(MouseButton::Middle)
+            || (left_pans && is_mouse_button_down(MouseButton::Left))
+        {
+            match self.camera.drag_start() {
+                // Pan from the followed position so the camera doesn't jump
+                Some(start) if self.follow && (start.distance(mouse) > CLICK_DISTANCE) => {
+                    self.follow = false;
+                    self.camera.start_drag(start);
+                }
+                _ => {}
+            }
+            if !self.follow {
+                self.camera.drag(mouse);
+            }
+        } else {
+            self.camera.end_drag();
+        }
+    }
+
+    /// Left click on a slime selects it when no obstacle tool is selected.
+    fn handle_selection(&mut self) {
+        let mouse = mouse_vec();
+        if OBSTACLE_TOOLS[self.obstacle_tool] != "None" {
+            self.click_start = None;
+            return;
+        }
+        if is_mouse_button_pressed(MouseButton::Left) && !root_ui().is_mouse_over(mouse) {
+            self.click_start = Some(mouse);
+        }
+        if is_mouse_button_released(MouseButton::Left) {
+            if let Some(start) = self.click_start.take() {
+                if start.distance(mouse) <= CLICK_DISTANCE {
+                    if let Some(i) = self.hovered_slime() {
+                        self.selected = Some(self.simulation.slime_controller.population.id[i]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Index of the selected slime, `None` if there is no selection or it died.
+    fn selected_slime(&self) -> Option<usize> {
+        let selected = self.selected?;
+        self.simulation
+            .slime_controller
+            .population
+            .id
+            .iter()
+            .position(|&id| id == selected)
+    }
+
+    /// Mouse position in world coordinates.
+    fn world_mouse(&self) -> Vec2 {
+        let mouse = self.camera.screen_to_world(mouse_vec(), screen_size());
+        if self.follow {
+            let size = self.simulation.world_size;
+            vec2(mouse.x.rem_euclid(size.x), mouse.y.rem_euclid(size.y))
+        } else {
+            mouse
+        }
+    }
+
+    /// Screen position of a world position, when following the copy of the wrapping
+    /// world closest to the center is used.
+    fn to_screen(&self, position: Vec2) -> Vec2 {
+        let position = if self.follow {
+            self.camera
+                .nearest_copy(position, self.simulation.world_size)
+        } else {
+            position
+        };
+        self.camera.world_to_screen(position, screen_size())
+    }
+
+    /// Centers the camera on the followed slime, switching to its largest living child
+    /// if it died and `follow_children` is set. Stops following when no slime is left.
+    fn update_follow(&mut self) {
+        if !self.follow {
+            return;
+        }
+        let population = &self.simulation.slime_controller.population;
+        if let (None, Some(selected), true) =
+            (self.selected_slime(), self.selected, self.follow_children)
+        {
+            let child = (0..population.len())
+                .filter(|&i| {
+                    population.parents[i].is_some_and(|(a, b)| a == selected || b == selected)
+                })
+                .max_by(|&a, &b| population.size[a].partial_cmp(&population.size[b]).unwrap());
+            if let Some(child) = child {
+                self.selected = Some(population.id[child]);
+            }
+        }
+        match self.selected_slime() {
+            Some(i) => {
+                self.camera.target = self.simulation.slime_controller.population.position[i];
+            }
+            // Nothing left to follow
+            None => {
+                self.follow = false;
+                self.selected = None;
+            }
+        }
+    }
+
+    /// Draws the world entities with the camera. When following, the world wraps around
+    /// so the copies across the edges are drawn too.
+    fn draw_world(&self) {
+        let screen = screen_size();
+        let camera = self.camera.camera_2d(screen);
+        let offsets = if self.follow {
+            self.camera.tile_offsets(self.simulation.world_size, screen)
+        } else {
+            vec![Vec2::ZERO]
+        };
+        for offset in offsets {
+            set_camera(&Camera2D {
+                target: camera.target - offset,
+                ..camera
+            });
+            self.draw_world_bounds();
+            self.simulation.biomes.draw();
+            self.simulation.scent.draw();
+            for (kind, _) in HeatmapKind::ALL
+                .iter()
+                .zip(self.heatmaps_visible)
+                .filter(|(_, visible)| *visible)
+            {
+                self.simulation.heatmaps.draw(*kind);
+            }
+            self.draw_obstacles();
+            self.draw_food();
+            if self.show_trails {
+                self.trails.draw(
+                    &self.simulation.slime_controller.population,
+                    self.simulation.world_size,
+                    1.0 / self.camera.zoom,
+                );
+            }
+            self.draw_slimes();
+        }
+    }
+
+    fn draw_world_bounds(&self) {
+        let size = self.simulation.world_size;
+        draw_rectangle_lines(0.0, 0.0, size.x, size.y, 2.0 / self.camera.zoom, DARKGRAY);
+    }
+
+    fn draw_obstacles(&self) {
+        self.simulation
+            .obstacles
+            .obstacles
+            .iter()
+            .for_each(|o| o.draw(OBSTACLE_COLOR));
+        // Obstacle being drawn
+        if let Some(obstacle) = self.drawn_obstacle(self.world_mouse()) {
+            obstacle.draw(Color::from_rgba(80, 80, 80, 150));
+        }
+    }
+
+    /// Builds the obstacle being drawn with the current tool from `obstacle_points`
+    /// up to `end`.
+    fn drawn_obstacle(&self, end: Vec2) -> Option<Obstacle> {
+        let start = *self.obstacle_points.first()?;
+        match OBSTACLE_TOOLS[self.obstacle_tool] {
+            "Rectangle" => Some(Obstacle::Rectangle {
+                position: start.min(end),
+                size: (end - start).abs(),
+            }),
+            "Circle" => Some(Obstacle::Circle {
+                center: start,
+                radius: start.distance(end),
+            }),
+            "Wall" => {
+                let mut points = self.obstacle_points.clone();
+                points.push(end);
+                Some(Obstacle::Polyline { points })
+            }
+            _ => None,
+        }
+    }
+
+    /// Draws obstacles with the mouse using the selected tool:
+    /// - Rectangle and circle: drag with the left button.
+    /// - Wall: left click to add points, right click to finish.
+    fn edit_obstacles(&mut self) {
+        if OBSTACLE_TOOLS[self.obstacle_tool] == "None" || root_ui().is_mouse_over(mouse_vec()) {
+            return;
+        }
+        let mouse = self.world_mouse();
+        if OBSTACLE_TOOLS[self.obstacle_tool] == "Wall" {
+            if is_mouse_button_pressed(MouseButton::Left) {
+                self.obstacle_points.push(mouse);
+            }
+            if is_mouse_button_pressed(MouseButton::Right) {
+                if self.obstacle_points.len() >= 2 {
+                    let points = std::mem::take(&mut self.obstacle_points);
+                    self.simulation
+                        .obstacles
+                        .push(Obstacle::Polyline { points });
+                }
+                self.obstacle_points.clear();
+            }
+        } else {
+            if is_mouse_button_pressed(MouseButton::Left) {
+                self.obstacle_points = vec![mouse];
+            }
+            if is_mouse_button_released(MouseButton::Left) {
+                if let Some(obstacle) = self.drawn_obstacle(mouse) {
+                    self.simulation.obstacles.push(obstacle);
+                }
+                self.obstacle_points.clear();
+            }
+        }
+    }
+
     fn draw_food(&self) {
-        self.food_controller
+        self.simulation
+            .food_controller
             .population
             .iter()
             .for_each(|f| draw_rectangle(f.position.x, f.position.y, FOOD_SIZE, FOOD_SIZE, GREEN));
     }
 
+    /// Index of the slime under the mouse, the one drawn on top if they overlap.
+    fn hovered_slime(&self) -> Option<usize> {
+        let mouse = self.world_mouse();
+        let controller = &self.simulation.slime_controller;
+        let population = &controller.population;
+        (0..population.len()).rev().find(|&i| {
+            population.is_point_inside(i, mouse, population.size_vision(i, &controller.config))
+        })
+    }
+
     fn draw_slimes(&self) {
-        let mouse = {
-            let (x, y) = mouse_position();
-            vec2(x, y)
-        };
-        self.slime_controller.population.iter().for_each(|slime| {
-            let hovered = slime.is_point_inside(mouse, slime.size_vision());
-            let color = if hovered {
+        let hovered = self.hovered_slime();
+        let controller = &self.simulation.slime_controller;
+        let population = &controller.population;
+        let thickness = 1.0 / self.camera.zoom;
+        for i in 0..population.len() {
+            let position = population.position[i];
+            if self.show_vision {
+                let range = population.size_vision(i, &controller.config);
+                draw_circle_lines(position.x, position.y, range, thickness, YELLOW);
+            }
+            if self.show_jump_range {
+                let range = population.size[i] + population.jump_distance(i, &controller.config);
+                draw_circle_lines(position.x, position.y, range, thickness, JUMP_RANGE_COLOR);
+            }
+        }
+        let palette = self.palette();
+        for i in 0..population.len() {
+            let (position, size) = (population.position[i], population.size[i]);
+            let color = if hovered == Some(i) {
                 BLUE
-            } else if slime.is_breed_ready(self.time, self.slime_controller.breeding_cooldown) {
-                PINK
             } else {
-                match slime.state {
-                    SlimeState::Normal => RED,
-                    SlimeState::Jumping => LIME,
-                    SlimeState::Breeding => VIOLET,
-                }
+                palette.color(i)
             };
-            draw_circle(slime.position.x, slime.position.y, slime.size(), color);
-            if hovered {
+            draw_circle(position.x, position.y, size, color);
+            if population.health[i].is_infected() {
                 draw_circle_lines(
-                    slime.position.x,
-                    slime.position.y,
-                    slime.size_vision(),
-                    1.0,
-                    YELLOW,
-                );
-                let text = format!("{:.0}", slime.energy());
-                const ENERGY_FONT_SIZE: u16 = 25;
-                let size = measure_text(&text, None, ENERGY_FONT_SIZE, 1.0);
-                draw_text(
-                    &format!("{:.0}", slime.energy()),
-                    slime.position.x - size.width.div(2.0),
-                    (slime.position.y - 10.0).max(0.0),
-                    ENERGY_FONT_SIZE as f32,
-                    WHITE,
+                    position.x,
+                    position.y,
+                    size + thickness,
+                    thickness,
+                    INFECTED_COLOR,
                 );
-                // Draw skill levels
-                const SKILLS_FONT_SIZE: u16 = 25;
-                const SKILLS_TEXT_PAD: f32 = 20.0;
-                let texts = [
-                    slime.skills.vision.to_string(),
-                    slime.skills.efficiency.to_string(),
-                    slime.skills.jumper.to_string(),
-                ];
-                let widths = texts
-                    .iter()
-                    .map(|s| measure_text(s, None, SKILLS_FONT_SIZE, 1.0).width)
-                    .collect::<Vec<_>>();
-                let width = widths.iter().sum::<f32>() + 2.0 * SKILLS_TEXT_PAD;
-                let mut x = slime.position.x - width / 2.0;
-                let y = (slime.position.y + 25.0).min(screen_height());
-                texts
-                    .iter()
-                    .zip([ORANGE, PURPLE, PINK])
-                    .zip(widths)
-                    .for_each(|((text, color), width)| {
-                        draw_text(text, x, y, SKILLS_FONT_SIZE as f32, color);
-                        x += width + SKILLS_TEXT_PAD;
-                    });
             }
-        });
+        }
+        if let Some(i) = self.selected_slime() {
+            let position = population.position[i];
+            draw_circle_lines(
+                position.x,
+                position.y,
+                population.size[i] + 2.0 / self.camera.zoom,
+                2.0 / self.camera.zoom,
+                SELECTED_COLOR,
+            );
+        }
+        if let Some(i) = hovered {
+            let position = population.position[i];
+            let size_vision = population.size_vision(i, &controller.config);
+            draw_circle_lines(
+                position.x,
+                position.y,
+                size_vision,
+                1.0 / self.camera.zoom,
+                YELLOW,
+            );
+        }
+    }
+
+    /// Draws the energy, skill levels and behavior of the hovered slime, in screen
+    /// coordinates so the text keeps its size at any zoom.
+    fn draw_hovered(&self) {
+        let i = match self.hovered_slime() {
+            Some(i) => i,
+            None => return,
+        };
+        let population = &self.simulation.slime_controller.population;
+        let position = self.to_screen(population.position[i]);
+        let text = format!("{:.0}", population.energy(i));
+        const ENERGY_FONT_SIZE: u16 = 25;
+        let size = measure_text(&text, None, ENERGY_FONT_SIZE, 1.0);
+        draw_text(
+            &text,
+            position.x - size.width.div(2.0),
+            (position.y - 10.0).max(0.0),
+            ENERGY_FONT_SIZE as f32,
+            WHITE,
+        );
+        // Draw skill levels
+        const SKILLS_FONT_SIZE: u16 = 25;
+        const SKILLS_TEXT_PAD: f32 = 20.0;
+        let skills = &population.skills[i];
+        let texts = [
+            skills.vision.to_string(),
+            skills.efficiency.to_string(),
+            skills.jumper.to_string(),
+        ];
+        let widths = texts
+            .iter()
+            .map(|s| measure_text(s, None, SKILLS_FONT_SIZE, 1.0).width)
+            .collect::<Vec<_>>();
+        let width = widths.iter().sum::<f32>() + 2.0 * SKILLS_TEXT_PAD;
+        let mut x = position.x - width / 2.0;
+        let y = (position.y + 25.0).min(screen_height());
+        texts
+            .iter()
+            .zip([ORANGE, PURPLE, PINK])
+            .zip(widths)
+            .for_each(|((text, color), width)| {
+                draw_text(text, x, y, SKILLS_FONT_SIZE as f32, color);
+                x += width + SKILLS_TEXT_PAD;
+            });
+        // Draw behavior
+        let text = population.behavior[i].name();
+        let size = measure_text(text, None, SKILLS_FONT_SIZE, 1.0);
+        draw_text(
+            text,
+            position.x - size.width.div(2.0),
+            (y + 20.0).min(screen_height()),
+            SKILLS_FONT_SIZE as f32,
+            LIGHTGRAY,
+        );
+    }
+
+    fn palette(&self) -> Palette<'_> {
+        Palette::new(
+            ColorMode::ALL[self.color_mode],
+            &self.simulation.slime_controller,
+            self.simulation.time,
+        )
+    }
+
+    /// Draws what the slime colors mean on the bottom right corner of the screen.
+    fn draw_legend(&self) {
+        const FONT_SIZE: u16 = 20;
+        const SWATCH_SIZE: f32 = 12.0;
+        let mut y = screen_height() - 10.0;
+        for (text, color) in self.palette().legend().into_iter().rev() {
+            let size = measure_text(&text, None, FONT_SIZE, 1.0);
+            let x = screen_width() - size.width - 5.0;
+            draw_text(&text, x, y, FONT_SIZE as f32, LIGHTGRAY);
+            draw_rectangle(
+                x - SWATCH_SIZE - 5.0,
+                y - SWATCH_SIZE,
+                SWATCH_SIZE,
+                SWATCH_SIZE,
+                color,
+            );
+            y -= size.height + 8.0;
+        }
     }
 
     /// Draws world status on top right corner of the screen
@@ -138,32 +775,35 @@ impl World {
         let time = Formatter::new()
             .with_decimals(1)
             .with_separator("")
-            .format(self.time as f64);
-        let vej = self
-            .slime_controller
-            .population
-            .iter()
-            .fold((0, 0, 0), |mut vej, s| {
-                vej.0 += s.skills.vision;
-                vej.1 += s.skills.efficiency;
-                vej.2 += s.skills.jumper;
-                vej
-            });
-        let entries = [
+            .format(self.simulation.time as f64);
+        let stats = self.simulation.stats();
+        let mut entries = vec![
             (format!("Fps: {}s", get_fps()), LIGHTGRAY),
+            (format!("Steps/s: {:.0}", self.clock.achieved()), LIGHTGRAY),
             (format!("Time: {}", time), LIGHTGRAY),
-            (
-                format!("Slimes: {}", self.slime_controller.population.len()),
-                LIGHTGRAY,
-            ),
-            (
-                format!("Food: {}", self.food_controller.population.len()),
-                LIGHTGRAY,
-            ),
-            (format!("Vision: {}", vej.0), ORANGE),
-            (format!("Efficiency: {}", vej.1), PURPLE),
-            (format!("Jumper: {}", vej.2), PINK),
+            (format!("Slimes: {}", stats.slimes), LIGHTGRAY),
+            (format!("Food: {}", stats.food), LIGHTGRAY),
+            (format!("Vision: {}", stats.vision), ORANGE),
+            (format!("Efficiency: {}", stats.efficiency), PURPLE),
+            (format!("Jumper: {}", stats.jumper), PINK),
         ];
+        if self.simulation.slime_controller.disease.enabled {
+            entries.push((format!("Infected: {}", stats.infected), INFECTED_COLOR));
+            entries.push((format!("Recovered: {}", stats.recovered), LIGHTGRAY));
+        }
+        if self.clock.paused {
+            entries.push(("Paused".to_string(), YELLOW));
+        }
+        if let Some(recorder) = &self.recorder {
+            let actions = recorder.replay.actions.len();
+            entries.push((format!("Recording: {} actions", actions), RED));
+        }
+        if self.player.is_some() {
+            entries.push(("Replaying".to_string(), YELLOW));
+        }
+        if let Some(reason) = &self.simulation.stopped {
+            entries.push((format!("Stopped: {}", reason), YELLOW));
+        }
         let mut y = 15.0;
         for (text, color) in entries {
             let size = measure_text(&text, None, FONT_SIZE, 1.0);
@@ -180,7 +820,7 @@ impl World {
 
     fn draw_ui(&mut self) {
         // All settings
-        widgets::Window::new(hash!(), vec2(5.0, 5.0), vec2(100.0, 25.0))
+        widgets::Window::new(hash!(), vec2(5.0, 5.0), vec2(330.0, 25.0))
             .movable(false)
             .titlebar(false)
             .ui(&mut *root_ui(), |ui| {
@@ -194,25 +834,92 @@ impl World {
                 ) {
                     self.settings_open = !self.settings_open;
                 }
+                ui.same_line(0.0);
+                if ui.button(None, "Fit world") {
+                    self.camera.fit(self.simulation.world_size, screen_size());
+                }
+                ui.same_line(0.0);
+                if ui.button(None, "Charts") {
+                    self.charts_open = !self.charts_open;
+                }
+                ui.same_line(0.0);
+                if ui.button(None, "Histograms") {
+                    self.histograms_open = !self.histograms_open;
+                }
             });
 
         if self.settings_open {
             widgets::Window::new(hash!(), vec2(5.0, 35.0), vec2(300.0, 300.0))
                 .label("Settings")
                 .ui(&mut *root_ui(), |ui| {
+                    ui.tree_node(hash!(), "Scenario", |ui| {
+                        let names = self
+                            .scenario_files
+                            .iter()
+                            .map(|p| p.file_stem().unwrap_or_default().to_string_lossy())
+                            .collect::<Vec<_>>();
+                        let names = names.iter().map(|n| n.as_ref()).collect::<Vec<_>>();
+                        if !names.is_empty() {
+                            ui.combo_box(hash!(), "File", &names, &mut self.scenario_index);
+                            if ui.button(None, "Load") {
+                                let path = self.scenario_files[self.scenario_index].clone();
+                                if let Err(e) =
+                                    Scenario::load(&path).and_then(|s| self.load_scenario(s))
+                                {
+                                    eprintln!(
+                                        "Failed to load scenario from {}: {}",
+                                        path.display(),
+                                        e
+                                    );
+                                }
+                            }
+                        }
+                        if ui.button(None, "Save current settings") {
+                            let saved = std::fs::create_dir_all(SCENARIOS_DIR)
+                                .map_err(|e| e.to_string())
+                                .and_then(|_| self.simulation.scenario().save(SAVED_SCENARIO_PATH));
+                            if let Err(e) = saved {
+                                eprintln!(
+                                    "Failed to save scenario to {}: {}",
+                                    SAVED_SCENARIO_PATH, e
+                                );
+                            }
+                            self.scenario_files = Scenario::list(SCENARIOS_DIR);
+                        }
+                    });
+                    ui.separator();
+                    ui.tree_node(hash!(), "Replay", |ui| {
+                        if self.recorder.is_some() {
+                            if ui.button(None, "Stop recording") {
+                                self.stop_recording();
+                            }
+                        } else if ui.button(None, "Record (restarts)") {
+                            if let Err(e) = self.start_recording() {
+                                eprintln!("Failed to start recording: {}", e);
+                            }
+                        }
+                        ui.same_line(0.0);
+                        if ui.button(None, "Play") {
+                            if let Err(e) = self.play_replay(REPLAY_PATH) {
+                                eprintln!("Failed to play replay from {}: {}", REPLAY_PATH, e);
+                            }
+                        }
+                        if self.player.is_some() {
+                            ui.same_line(0.0);
+                            if ui.button(None, "Stop replay") {
+                                self.player = None;
+                            }
+                        }
+                    });
+                    ui.separator();
                     ui.tree_node(hash!(), "Initial settings", |ui| {
-                        ui.slider(
-                            hash!(),
-                            "Food instances",
-                            0.0..1000.0,
-                            &mut self.initial_food,
-                        );
-                        ui.slider(
-                            hash!(),
-                            "Slime instances",
-                            0.0..1000.0,
-                            &mut self.initial_slimes,
-                        );
+                        let scenario = &mut self.simulation.scenario;
+                        let mut initial_food = scenario.initial_food as f32;
+                        ui.slider(hash!(), "Food instances", 0.0..1000.0, &mut initial_food);
+                        scenario.initial_food = initial_food as usize;
+                        let mut initial_slimes = scenario.initial_slimes as f32;
+                        ui.slider(hash!(), "Slime instances", 0.0..1000.0, &mut initial_slimes);
+                        scenario.initial_slimes = initial_slimes as usize;
                     });
                     ui.separator();
                     ui.tree_node(hash!(), "Food", |ui| {
@@ -220,37 +927,37 @@ impl World {
                             hash!(),
                             "Spawn time",
                             1.0..20.0,
-                            &mut self.food_controller.spawn_time,
+                            &mut self.simulation.food_controller.spawn_time,
                         );
                         ui.slider(
                             hash!(),
                             "Limit",
                             0.0..1000.0,
-                            &mut self.food_controller.limit,
+                            &mut self.simulation.food_controller.limit,
                         );
                         ui.slider(
                             hash!(),
                             "Min energy",
-                            0.0..self.food_controller.energy_range.1,
-                            &mut self.food_controller.energy_range.0,
+                            0.0..self.simulation.food_controller.energy_range.1,
+                            &mut self.simulation.food_controller.energy_range.0,
                         );
                         ui.slider(
                             hash!(),
                             "Max energy",
-                            self.food_controller.energy_range.0 + 1e-3..100.0,
-                            &mut self.food_controller.energy_range.1,
+                            self.simulation.food_controller.energy_range.0 + 1e-3..100.0,
+                            &mut self.simulation.food_controller.energy_range.1,
                         );
                         ui.slider(
                             hash!(),
                             "Min speed",
-                            0.0..self.food_controller.speed_range.1,
-                            &mut self.food_controller.speed_range.0,
+                            0.0..self.simulation.food_controller.speed_range.1,
+                            &mut self.simulation.food_controller.speed_range.0,
                         );
                         ui.slider(
                             hash!(),
                             "Max speed",
-                            self.food_controller.speed_range.0 + 1e-3..10.0,
-                            &mut self.food_controller.speed_range.1,
+                            self.simulation.food_controller.speed_range.0 + 1e-3..10.0,
+                            &mut self.simulation.food_controller.speed_range.1,
                         );
                     });
                     ui.separator();
@@ -259,112 +966,598 @@ impl World {
                             hash!(),
                             "Cost frequency",
                             0.01..50.0,
-                            &mut self.slime_controller.time_cost_freq,
+                            &mut self.simulation.slime_controller.time_cost_freq,
                         );
                         ui.slider(
                             hash!(),
                             "Speed factor",
                             0.0..10.0,
-                            &mut self.slime_controller.config.speed_factor,
+                            &mut self.simulation.slime_controller.config.speed_factor,
                         );
                         ui.slider(
                             hash!(),
                             "Initial energy",
                             5.0..100.0,
-                            &mut self.slime_controller.config.initial_energy,
+                            &mut self.simulation.slime_controller.config.initial_energy,
                         );
                         ui.slider(
                             hash!(),
                             "Step cost",
                             0.0..10.0,
-                            &mut self.slime_controller.config.step_cost,
+                            &mut self.simulation.slime_controller.config.step_cost,
                         );
                         ui.slider(
                             hash!(),
                             "Vision range",
                             10.0..200.0,
-                            &mut self.slime_controller.config.vision_range,
+                            &mut self.simulation.slime_controller.config.vision_range,
                         );
                         ui.slider(
                             hash!(),
                             "Jump cooldown",
                             50.0..2500.0,
-                            &mut self.slime_controller.config.jump_cooldown,
+                            &mut self.simulation.slime_controller.config.jump_cooldown,
                         );
                         ui.slider(
                             hash!(),
                             "Breeding cooldown",
                             50.0..3000.0,
-                            &mut self.slime_controller.breeding_cooldown,
+                            &mut self.simulation.slime_controller.breeding_cooldown,
                         );
                         ui.slider(
                             hash!(),
                             "Max size slow",
                             0.0..1.0,
-                            &mut self.slime_controller.max_size_slow,
+                            &mut self.simulation.slime_controller.max_size_slow,
+                        );
+                        ui.checkbox(
+                            hash!(),
+                            "Collisions",
+                            &mut self.simulation.slime_controller.collisions,
+                        );
+                        ui.slider(
+                            hash!(),
+                            "Crowding cost",
+                            0.0..1.0,
+                            &mut self.simulation.slime_controller.crowding_cost,
+                        );
+                        ui.slider(
+                            hash!(),
+                            "Crowding radius",
+                            0.0..100.0,
+                            &mut self.simulation.slime_controller.crowding_radius,
                         );
                     });
                     ui.separator();
+                    ui.tree_node(hash!(), "Behavior", |ui| {
+                        ui.slider(
+                            hash!(),
+                            "Wander turn",
+                            0.0..1.0,
+                            &mut self.simulation.slime_controller.behavior_rules.wander_turn,
+                        );
+                        ui.slider(
+                            hash!(),
+                            "Rest duration",
+                            0.0..1000.0,
+                            &mut self
+                                .simulation
+                                .slime_controller
+                                .behavior_rules
+                                .rest_duration,
+                        );
+                        ui.slider(
+                            hash!(),
+                            "Rest time cost",
+                            0.0..1.0,
+                            &mut self
+                                .simulation
+                                .slime_controller
+                                .behavior_rules
+                                .rest_time_cost,
+                        );
+                        ui.slider(
+                            hash!(),
+                            "Flee size ratio",
+                            0.0..5.0,
+                            &mut self
+                                .simulation
+                                .slime_controller
+                                .behavior_rules
+                                .flee_size_ratio,
+                        );
+                    });
+                    ui.separator();
+                    ui.tree_node(hash!(), "Group", |ui| {
+                        let rules = &mut self.simulation.slime_controller.group_rules;
+                        ui.checkbox(hash!(), "Flocking", &mut rules.flocking);
+                        ui.slider(
+                            hash!(),
+                            "Flock strength",
+                            0.0..5.0,
+                            &mut rules.flock_strength,
+                        );
+                        ui.checkbox(hash!(), "Kin sharing", &mut rules.kin_sharing);
+                        ui.slider(
+                            hash!(),
+                            "Starving energy",
+                            0.0..100.0,
+                            &mut rules.starving_energy,
+                        );
+                        ui.slider(hash!(), "Share energy", 0.0..10.0, &mut rules.share_energy);
+                        ui.slider(
+                            hash!(),
+                            "Share distance",
+                            0.0..100.0,
+                            &mut rules.share_distance,
+                        );
+                    });
+                    ui.separator();
+                    ui.tree_node(hash!(), "Disease", |ui| {
+                        let disease = &mut self.simulation.slime_controller.disease;
+                        ui.checkbox(hash!(), "Enabled", &mut disease.enabled);
+                        ui.slider(hash!(), "Energy cost", 0.0..1.0, &mut disease.energy_cost);
+                        ui.slider(
+                            hash!(),
+                            "Contact chance",
+                            0.0..1.0,
+                            &mut disease.contact_chance,
+                        );
+                        ui.slider(hash!(), "Breed chance", 0.0..1.0, &mut disease.breed_chance);
+                        ui.slider(hash!(), "Duration", 0.0..10000.0, &mut disease.duration);
+                        if ui.button(None, "Infect slime") {
+                            self.act(Action::InfectSlime);
+                        }
+                    });
+                    ui.separator();
                     ui.tree_node(hash!(), "Skills", |ui| {
                         ui.slider(
                             hash!(),
                             "Vision",
                             0.0..10.0,
-                            &mut self.slime_controller.config.vision_skill,
+                            &mut self.simulation.slime_controller.config.vision_skill,
                         );
                         ui.slider(
                             hash!(),
                             "Efficiency",
                             0.0..20.0,
-                            &mut self.slime_controller.config.efficiency_skill,
+                            &mut self.simulation.slime_controller.config.efficiency_skill,
                         );
                         ui.slider(
                             hash!(),
                             "Jumper",
                             0.0..100.0,
-                            &mut self.slime_controller.config.jumper_skill,
+                            &mut self.simulation.slime_controller.config.jumper_skill,
+                        );
+                    });
+                    ui.separator();
+                    ui.tree_node(hash!(), "Biomes", |ui| {
+                        ui.checkbox(hash!(), "Enabled", &mut self.simulation.biomes.enabled);
+                        if ui.button(None, "Generate") {
+                            self.act(Action::GenerateBiomes);
+                        }
+                        for (biome, modifiers) in Biome::ALL
+                            .iter()
+                            .zip(self.simulation.biomes.modifiers.iter_mut())
+                        {
+                            ui.tree_node(hash!(biome.name()), biome.name(), |ui| {
+                                ui.slider(
+                                    hash!(biome.name(), "food"),
+                                    "Food spawn",
+                                    0.0..3.0,
+                                    &mut modifiers.food_spawn,
+                                );
+                                ui.slider(
+                                    hash!(biome.name(), "cost"),
+                                    "Step cost",
+                                    0.0..3.0,
+                                    &mut modifiers.step_cost,
+                                );
+                                ui.slider(
+                                    hash!(biome.name(), "speed"),
+                                    "Speed",
+                                    0.0..3.0,
+                                    &mut modifiers.speed,
+                                );
+                                ui.slider(
+                                    hash!(biome.name(), "vision"),
+                                    "Vision",
+                                    0.0..3.0,
+                                    &mut modifiers.vision,
+                                );
+                            });
+                        }
+                    });
+                    ui.separator();
+                    ui.tree_node(hash!(), "Scent", |ui| {
+                        ui.checkbox(hash!(), "Enabled", &mut self.simulation.scent.enabled);
+                        ui.checkbox(hash!(), "Show overlay", &mut self.simulation.scent.visible);
+                        ui.slider(
+                            hash!(),
+                            "Diffusion",
+                            0.0..1.0,
+                            &mut self.simulation.scent.diffusion,
+                        );
+                        ui.slider(
+                            hash!(),
+                            "Evaporation",
+                            0.0..0.2,
+                            &mut self.simulation.scent.evaporation,
+                        );
+                        ui.slider(
+                            hash!(),
+                            "Eat deposit",
+                            0.0..200.0,
+                            &mut self.simulation.scent.eat_deposit,
+                        );
+                        ui.slider(
+                            hash!(),
+                            "Food emission",
+                            0.0..5.0,
+                            &mut self.simulation.scent.food_emission,
+                        );
+                    });
+                    ui.separator();
+                    ui.tree_node(hash!(), "Events", |ui| {
+                        let mut triggered = None;
+                        for (i, event) in self.ui_events.iter_mut().enumerate() {
+                            match event {
+                                Event::Famine { duration } => {
+                                    ui.slider(hash!(i), "Duration", 0.0..10000.0, duration);
+                                }
+                                Event::Meteor { radius, .. } => {
+                                    ui.slider(hash!(i), "Radius", 0.0..300.0, radius);
+                                }
+                                Event::Bloom { amount } | Event::Plague { infected: amount } => {
+                                    let mut value = *amount as f32;
+                                    ui.slider(hash!(i), "Amount", 0.0..300.0, &mut value);
+                                    *amount = value as usize;
+                                }
+                                Event::Migration { amount, skills } => {
+                                    let mut values =
+                                        [*amount, skills.0, skills.1, skills.2].map(|v| v as f32);
+                                    for (j, (label, value)) in
+                                        ["Amount", "Vision", "Efficiency", "Jumper"]
+                                            .iter()
+                                            .zip(values.iter_mut())
+                                            .enumerate()
+                                    {
+                                        ui.slider(hash!(i, j), label, 0.0..30.0, value);
+                                    }
+                                    let [a, v, e, j] = values.map(|v| v as usize);
+                                    *amount = a;
+                                    *skills = (v, e, j);
+                                }
+                            }
+                            if ui.button(None, event.name()) {
+                                triggered = Some(event.clone());
+                            }
+                            ui.separator();
+                        }
+                        if let Some(event) = triggered {
+                            self.act(Action::Event { event });
+                        }
+                    });
+                    ui.separator();
+                    ui.tree_node(hash!(), "Display", |ui| {
+                        ui.checkbox(hash!(), "Trails", &mut self.show_trails);
+                        let mut length = self.trails.length as f32;
+                        ui.slider(hash!(), "Trail length", 2.0..300.0, &mut length);
+                        self.trails.length = length as usize;
+                        ui.checkbox(hash!(), "Vision ranges", &mut self.show_vision);
+                        ui.checkbox(hash!(), "Jump ranges", &mut self.show_jump_range);
+                        let modes = ColorMode::ALL.map(|mode| mode.name());
+                        ui.combo_box(hash!(), "Color by", &modes, &mut self.color_mode);
+                    });
+                    ui.separator();
+                    ui.tree_node(hash!(), "Heatmaps", |ui| {
+                        for (kind, visible) in HeatmapKind::ALL
+                            .iter()
+                            .zip(self.heatmaps_visible.iter_mut())
+                        {
+                            ui.checkbox(hash!(kind.name()), kind.name(), visible);
+                        }
+                        ui.checkbox(hash!(), "Record hidden", &mut self.record_heatmaps);
+                        ui.slider(
+                            hash!(),
+                            "Window",
+                            100.0..50000.0,
+                            &mut self.simulation.heatmaps.window,
                         );
+                        if ui.button(None, "Export PNG") {
+                            for kind in HeatmapKind::ALL {
+                                let name = kind.name().to_lowercase().replace(' ', "_");
+                                let path = HEATMAP_PATH.replace("{}", &name);
+                                if let Err(e) = self.simulation.heatmaps.export_png(kind, &path) {
+                                    eprintln!("Failed to export heatmap to {}: {}", path, e);
+                                }
+                            }
+                        }
+                    });
+                    ui.separator();
+                    ui.tree_node(hash!(), "Obstacles", |ui| {
+                        let tool =
+                            ui.combo_box(hash!(), "Draw", &OBSTACLE_TOOLS, &mut self.obstacle_tool);
+                        if OBSTACLE_TOOLS[tool] == "None" {
+                            self.obstacle_points.clear();
+                        }
+                        if ui.button(None, "Clear obstacles") {
+                            self.simulation.obstacles.clear();
+                        }
                     });
                     ui.separator();
                     if ui.button(None, "Reset") {
-                        self.reset();
+                        self.act(Action::Reset);
                     }
                     if ui.button(None, "Spawn food") {
-                        self.food_controller.spawn_one();
+                        self.act(Action::SpawnFood);
                     }
                     if ui.button(None, "Spawn slime") {
-                        self.slime_controller.spawn_one();
+                        self.act(Action::SpawnSlime);
+                    }
+                    if ui.button(None, "Export stats") {
+                        if let Err(e) = self.simulation.stats_recorder.export_csv(STATS_PATH) {
+                            eprintln!("Failed to export stats to {}: {}", STATS_PATH, e);
+                        }
                     }
                 });
-            self.slime_controller.update_slime_configs();
         }
         // Simulation speed
         widgets::Window::new(
             hash!(),
-            vec2(25.0, screen_height() - 35.0 - 25.0),
-            vec2(300.0, 35.0),
+            vec2(25.0, screen_height() - 190.0 - 25.0),
+            vec2(300.0, 190.0),
         )
         .label("Simulation speed")
         .ui(&mut *root_ui(), |ui| {
             ui.slider(
                 hash!(),
-                "[1 .. 500]",
-                1.0..500.0,
-                &mut self.simulation_speed,
+                "Steps/s",
+                1.0..5000.0,
+                &mut self.clock.steps_per_second,
+            );
+            ui.slider(hash!(), "Budget (ms)", 1.0..100.0, &mut self.clock.budget);
+            ui.checkbox(hash!(), "Max speed", &mut self.clock.max_speed);
+            ui.slider(
+                hash!(),
+                "Max speed fps",
+                1.0..30.0,
+                &mut self.clock.max_speed_fps,
+            );
+            ui.separator();
+            let pause = if self.clock.paused { "Resume" } else { "Pause" };
+            if ui.button(None, pause) {
+                self.clock.paused = !self.clock.paused;
+            }
+            ui.same_line(0.0);
+            if ui.button(None, "Step") {
+                self.step(1);
+            }
+            ui.same_line(0.0);
+            if ui.button(None, "Step N") {
+                self.step(self.step_n.round() as usize);
+            }
+            ui.same_line(0.0);
+            if ui.button(None, "Rewind") {
+                self.rewind();
+            }
+            ui.slider(hash!(), "N", 1.0..5000.0, &mut self.step_n);
+            ui.slider(
+                hash!(),
+                "Rewind steps",
+                1.0..10000.0,
+                &mut self.rewind_steps,
+            );
+            ui.label(
+                None,
+                &format!("Rewind up to time {:.0}", self.history.oldest_time()),
             );
         });
+        self.draw_inspector();
+        self.draw_charts();
+        self.draw_histograms();
     }
 
-    /// Resets simulation
-    fn reset(&mut self) {
-        self.food_controller.population.clear();
-        self.food_controller.spawn_n(self.initial_food as usize);
-        self.food_controller.last_spawn_time = 0.0;
-        self.slime_controller.population.clear();
-        self.slime_controller.spawn_n(self.initial_slimes as usize);
-        self.slime_controller.last_time_cost = 0.0;
-        self.time = 0.0;
+    /// Collects the distributions every `histograms_steps` while they are shown.
+    fn update_distributions(&mut self) {
+        if !self.histograms_open {
+            return;
+        }
+        let time = self.simulation.time;
+        if self.distributions.as_ref().is_none_or(|d| {
+            // The simulation may have gone back in time
+            (time < d.time) || (time - d.time) >= self.histograms_steps
+        }) {
+            self.distributions = Some(Distributions::collect(
+                time,
+                &self.simulation.slime_controller.population,
+            ));
+        }
     }
+
+    fn draw_histograms(&mut self) {
+        if !self.histograms_open {
+            return;
+        }
+        let distributions = self.distributions.clone().unwrap_or_default();
+        let range = |name: &str, h: &Histogram| format!("{}: {:.1} to {:.1}", name, h.min, h.max);
+        let max_level = |h: &Histogram| h.counts.len().max(1) - 1;
+        widgets::Window::new(
+            hash!(),
+            vec2(screen_width() - 940.0, screen_height() - 610.0).max(Vec2::ZERO),
+            vec2(310.0, 600.0),
+        )
+        .label("Histograms")
+        .ui(&mut root_ui(), |ui| {
+            ui.slider(
+                hash!(),
+                "Update steps",
+                1.0..5000.0,
+                &mut self.histograms_steps,
+            );
+            for (name, histogram, color) in [
+                ("Energy", &distributions.energy, YELLOW),
+                ("Size", &distributions.size, RED),
+                ("Age", &distributions.age, LIGHTGRAY),
+            ] {
+                ui.label(None, &range(name, histogram));
+                histogram_chart(&mut ui.canvas(), HISTOGRAM_SIZE, &histogram.counts, color);
+            }
+            for (name, histogram, color) in [
+                ("Vision", &distributions.vision, ORANGE),
+                ("Efficiency", &distributions.efficiency, PURPLE),
+                ("Jumper", &distributions.jumper, PINK),
+            ] {
+                ui.label(
+                    None,
+                    &format!("{} level: 0 to {}", name, max_level(histogram)),
+                );
+                histogram_chart(&mut ui.canvas(), HISTOGRAM_SIZE, &histogram.counts, color);
+            }
+            ui.label(None, "Vision (x) vs efficiency (y) level");
+            count_scatter_chart(
+                &mut ui.canvas(),
+                CHART_SIZE * vec2(1.0, 2.0),
+                distributions
+                    .vision_efficiency
+                    .iter()
+                    .map(|(&levels, &count)| (levels, count)),
+                (
+                    max_level(&distributions.vision),
+                    max_level(&distributions.efficiency),
+                ),
+                SKYBLUE,
+            );
+        });
+    }
+
+    /// Charts of the recorded stats over the last `charts_window` time steps.
+    fn draw_charts(&mut self) {
+        if !self.charts_open {
+            return;
+        }
+        let start = self.simulation.time - self.charts_window;
+        let records = &self.simulation.stats_recorder.records;
+        let records = &records[records.partition_point(|r| r.time < start)..];
+        let series = |f: fn(&Stats) -> f32| records.iter().map(f).collect::<Vec<_>>();
+        let slimes = series(|r| r.slimes as f32);
+        let food = series(|r| r.food as f32);
+        let energy = series(|r| r.mean_energy);
+        let vision = series(|r| r.vision as f32);
+        let efficiency = series(|r| r.efficiency as f32);
+        let jumper = series(|r| r.jumper as f32);
+        let last = |values: &[f32]| values.last().cloned().unwrap_or_default();
+        widgets::Window::new(
+            hash!(),
+            vec2(screen_width() - 620.0, screen_height() - 480.0).max(Vec2::ZERO),
+            vec2(310.0, 470.0),
+        )
+        .label("Charts")
+        .ui(&mut root_ui(), |ui| {
+            ui.slider(hash!(), "Window", 1000.0..100000.0, &mut self.charts_window);
+            ui.label(None, &format!("Slimes: {:.0}", last(&slimes)));
+            line_chart(&mut ui.canvas(), CHART_SIZE, &slimes, RED);
+            ui.label(None, &format!("Food: {:.0}", last(&food)));
+            line_chart(&mut ui.canvas(), CHART_SIZE, &food, GREEN);
+            ui.label(None, &format!("Mean energy: {:.1}", last(&energy)));
+            line_chart(&mut ui.canvas(), CHART_SIZE, &energy, YELLOW);
+            ui.label(None, "Skills: vision, efficiency, jumper");
+            stacked_area_chart(
+                &mut ui.canvas(),
+                CHART_SIZE * vec2(1.0, 2.0),
+                &[(&vision, ORANGE), (&efficiency, PURPLE), (&jumper, PINK)],
+            );
+        });
+    }
+
+    /// Window with the details of the selected slime, updated every frame.
+    fn draw_inspector(&mut self) {
+        let selected = match self.selected {
+            Some(selected) => selected,
+            None => return,
+        };
+        let lines = match self.selected_slime() {
+            Some(i) => self.inspect(i),
+            None => vec![format!("Slime {} is dead", selected)],
+        };
+        let mut close = false;
+        widgets::Window::new(
+            hash!(),
+            vec2(screen_width() - 265.0, 300.0),
+            vec2(260.0, 380.0),
+        )
+        .label("Inspector")
+        .ui(&mut root_ui(), |ui| {
+            for line in lines.iter() {
+                ui.label(None, line);
+            }
+            ui.separator();
+            ui.checkbox(hash!(), "Follow", &mut self.follow);
+            ui.checkbox(hash!(), "Follow child on death", &mut self.follow_children);
+            close = ui.button(None, "Close");
+        });
+        if close {
+            self.selected = None;
+            self.follow = false;
+        }
+    }
+
+    /// Lines describing the slime at index `i`.
+    fn inspect(&self, i: usize) -> Vec<String> {
+        let time = self.simulation.time;
+        let controller = &self.simulation.slime_controller;
+        let config = &controller.config;
+        let population = &controller.population;
+        let ready = |ready: bool| if ready { "ready" } else { "not ready" };
+        let next_skill_goal = if population.next_skill_goal[i] == f32::MAX {
+            "maxed".to_string()
+        } else {
+            format!("{:.0} energy", population.next_skill_goal[i])
+        };
+        let parents = match population.parents[i] {
+            Some((a, b)) => format!("{} and {}", a, b),
+            None => "none".to_string(),
+        };
+        let skills = &population.skills[i];
+        vec![
+            format!("ID: {}", population.id[i]),
+            format!("Age: {:.0}", time - population.born[i]),
+            format!("Energy: {:.1}", population.energy(i)),
+            format!("Size: {:.2}", population.size(i)),
+            format!(
+                "Speed factor: {:.2}",
+                population.speed_factor(i, config, controller.max_size_slow)
+            ),
+            format!("Vision range: {:.1}", population.vision_range(i, config)),
+            format!("Step cost: {:.3}", population.step_cost(i, config)),
+            format!(
+                "Jump cooldown: {:.0} ({})",
+                population.jump_cooldown(i, config),
+                ready(population.is_jump_ready(i, time, config))
+            ),
+            format!(
+                "Breeding: {}",
+                ready(population.is_breed_ready(i, time, controller.breeding_cooldown, config))
+            ),
+            format!("Next skill goal: {}", next_skill_goal),
+            format!("Skill path: {:?}", population.skill_path(i)),
+            format!(
+                "Skills: vision {}, efficiency {}, jumper {}",
+                skills.vision, skills.efficiency, skills.jumper
+            ),
+            format!("Parents: {}", parents),
+            format!("Children: {}", population.children[i]),
+        ]
+    }
+}
+
+pub fn screen_size() -> Vec2 {
+    vec2(screen_width(), screen_height())
+}
+
+fn mouse_vec() -> Vec2 {
+    let (x, y) = mouse_position();
+    vec2(x, y)
 }
 
 fn setup_skin() {